@@ -1,12 +1,6 @@
 #[cfg(feature = "async")]
 use laneful_rs::{Email, LanefulClient, LanefulError, Result};
 
-#[cfg(feature = "async")]
-fn env_var(name: &str) -> Result<String> {
-    std::env::var(name)
-        .map_err(|_| LanefulError::ConfigError(format!("{name} is required (set it in your env)")))
-}
-
 #[cfg(feature = "async")]
 fn arg_var(name: &str) -> Option<String> {
     let mut args = std::env::args().skip(1);
@@ -28,12 +22,10 @@ fn required_arg(name: &str) -> Result<String> {
 #[cfg(feature = "async")]
 #[tokio::main]
 async fn main() -> Result<()> {
-    let endpoint = env_var("LANEFUL_ENDPOINT")?;
-    let api_key = env_var("LANEFUL_API_KEY")?;
     let from = required_arg("--from")?;
     let to = required_arg("--to")?;
 
-    let client = LanefulClient::new(endpoint, api_key)?;
+    let client = LanefulClient::from_env()?;
 
     let email = Email::builder()
         .from(from, Some("Sender"))
@@ -4,13 +4,15 @@ use axum::{
     http::{HeaderMap, StatusCode},
     routing::post,
 };
-use laneful_rs::verify_webhook_signature;
+use laneful_rs::WebhookVerifier;
 
 const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
 
 async fn webhook_handler(headers: HeaderMap, body: Bytes) -> StatusCode {
-    let secret = std::env::var("LANEFUL_WEBHOOK_SECRET")
-        .expect("LANEFUL_WEBHOOK_SECRET env var is required");
+    let verifier = WebhookVerifier::new(
+        std::env::var("LANEFUL_WEBHOOK_SECRET")
+            .expect("LANEFUL_WEBHOOK_SECRET env var is required"),
+    );
 
     let signature = match headers.get(SIGNATURE_HEADER) {
         Some(sig) => sig.to_str().unwrap_or_default(),
@@ -20,7 +22,7 @@ async fn webhook_handler(headers: HeaderMap, body: Bytes) -> StatusCode {
         }
     };
 
-    if !verify_webhook_signature(&secret, &body, signature) {
+    if !verifier.verify(&body, signature) {
         println!("Invalid webhook signature");
         return StatusCode::UNAUTHORIZED;
     }
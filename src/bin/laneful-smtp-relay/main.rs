@@ -0,0 +1,177 @@
+//! SMTP relay forwarding messages to the Laneful API, for applications that can only
+//! send mail over SMTP.
+//!
+//! The Laneful client is configured like any other (`LANEFUL_CONFIG`, `LANEFUL_ENDPOINT`,
+//! `LANEFUL_API_KEY`, ...; see `LanefulConfig`). The relay itself reads:
+//!
+//! | Variable | Default |
+//! |---|---|
+//! | `LANEFUL_SMTP_LISTEN` | `127.0.0.1:2525` |
+//! | `LANEFUL_SMTP_HOSTNAME` | `localhost` |
+//! | `LANEFUL_SMTP_USERNAME`, `LANEFUL_SMTP_PASSWORD` | none: AUTH is not required |
+//! | `LANEFUL_SMTP_TLS_CERT`, `LANEFUL_SMTP_TLS_KEY` | none: STARTTLS is not offered |
+//! | `LANEFUL_SMTP_MAX_MESSAGE_SIZE` | `26214400` (25 MiB) |
+//! | `LANEFUL_SMTP_BATCH_SIZE` | `50` |
+//! | `LANEFUL_SMTP_BATCH_WAIT_MS` | `200` |
+
+mod session;
+#[cfg(test)]
+mod tests;
+
+use laneful_rs::{EmailQueue, LanefulClient, LanefulConfig, LanefulError, Result, RetryPolicy};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+const USAGE: &str = "\
+Usage: laneful-smtp-relay
+
+Accepts mail over SMTP and forwards it to the Laneful API.
+
+The client is configured with LANEFUL_CONFIG, LANEFUL_ENDPOINT, LANEFUL_API_KEY, etc.
+The relay reads:
+  LANEFUL_SMTP_LISTEN                           listen address (default: 127.0.0.1:2525)
+  LANEFUL_SMTP_HOSTNAME                         name in greetings (default: localhost)
+  LANEFUL_SMTP_USERNAME, LANEFUL_SMTP_PASSWORD  require AUTH with these credentials
+  LANEFUL_SMTP_TLS_CERT, LANEFUL_SMTP_TLS_KEY   PEM files enabling STARTTLS
+  LANEFUL_SMTP_MAX_MESSAGE_SIZE                 in bytes (default: 26214400)
+  LANEFUL_SMTP_BATCH_SIZE                       emails per API request (default: 50)
+  LANEFUL_SMTP_BATCH_WAIT_MS                    wait for a batch to fill (default: 200)";
+
+/// Retries used when the configuration does not set any.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Settings shared by all SMTP sessions.
+pub(crate) struct Relay {
+    pub(crate) hostname: String,
+    pub(crate) credentials: Option<(String, String)>,
+    pub(crate) tls: Option<TlsAcceptor>,
+    pub(crate) max_message_size: usize,
+    pub(crate) queue: EmailQueue,
+}
+
+fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str, default: T) -> Result<T> {
+    match var(name) {
+        Some(value) => value.trim().parse().map_err(|_| {
+            LanefulError::ConfigError(format!("{name} must be a number, got {value:?}"))
+        }),
+        None => Ok(default),
+    }
+}
+
+fn tls_acceptor() -> Result<Option<TlsAcceptor>> {
+    let (cert, key) = match (var("LANEFUL_SMTP_TLS_CERT"), var("LANEFUL_SMTP_TLS_KEY")) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => {
+            return Err(LanefulError::ConfigError(
+                "LANEFUL_SMTP_TLS_CERT and LANEFUL_SMTP_TLS_KEY must be set together".into(),
+            ));
+        }
+    };
+    let invalid = |err: &dyn std::fmt::Display| {
+        LanefulError::ConfigError(format!("invalid TLS certificate or key: {err}"))
+    };
+
+    let certs = CertificateDer::pem_file_iter(&cert)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|err| invalid(&err))?;
+    let key = PrivateKeyDer::from_pem_file(&key).map_err(|err| invalid(&err))?;
+    let config = ServerConfig::builder_with_provider(Arc::new(
+        tokio_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|err| invalid(&err))?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|err| invalid(&err))?;
+    Ok(Some(TlsAcceptor::from(Arc::new(config))))
+}
+
+fn client() -> Result<LanefulClient> {
+    let config = LanefulConfig::load()?;
+    let mut client = LanefulClient::from_config(&config)?.auto_idempotency_keys(true);
+    if var("LANEFUL_MAX_RETRIES").is_none() && config.retry == RetryPolicy::default() {
+        client = client.retry_policy(RetryPolicy::new(DEFAULT_MAX_RETRIES));
+    }
+    Ok(client)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let credentials = match (var("LANEFUL_SMTP_USERNAME"), var("LANEFUL_SMTP_PASSWORD")) {
+        (Some(username), Some(password)) => Some((username, password)),
+        (None, None) => None,
+        _ => {
+            return Err(LanefulError::ConfigError(
+                "LANEFUL_SMTP_USERNAME and LANEFUL_SMTP_PASSWORD must be set together".into(),
+            ));
+        }
+    };
+    let queue = EmailQueue::builder(client()?)
+        .max_batch_size(parse_var("LANEFUL_SMTP_BATCH_SIZE", 50)?)
+        .max_wait(Duration::from_millis(parse_var(
+            "LANEFUL_SMTP_BATCH_WAIT_MS",
+            200,
+        )?))
+        .build();
+    let relay = Arc::new(Relay {
+        hostname: var("LANEFUL_SMTP_HOSTNAME").unwrap_or_else(|| "localhost".into()),
+        credentials,
+        tls: tls_acceptor()?,
+        max_message_size: parse_var("LANEFUL_SMTP_MAX_MESSAGE_SIZE", 25 * 1024 * 1024)?,
+        queue,
+    });
+
+    let addr = var("LANEFUL_SMTP_LISTEN").unwrap_or_else(|| "127.0.0.1:2525".into());
+    let listener = TcpListener::bind(&addr).await?;
+    println!(
+        "SMTP relay listening on {} (STARTTLS: {}, AUTH: {})",
+        listener.local_addr()?,
+        if relay.tls.is_some() { "on" } else { "off" },
+        if relay.credentials.is_some() {
+            "required"
+        } else {
+            "off"
+        },
+    );
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        eprintln!("accept failed: {err}");
+                        continue;
+                    }
+                };
+                let relay = relay.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = session::run(stream, &relay).await {
+                        eprintln!("session with {peer} failed: {err}");
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("shutting down");
+                relay.queue.close();
+                relay.queue.closed().await;
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! One SMTP session (RFC 5321), with optional STARTTLS (RFC 3207) and AUTH PLAIN/LOGIN
+//! (RFC 4954).
+
+use crate::Relay;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use laneful_rs::{Email, LanefulError, SendEmailResponse, SendStatus};
+use std::borrow::Cow;
+use std::io;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// Maximum length of a command line.
+const MAX_LINE: u64 = 4096;
+/// Maximum recipients of one message.
+const MAX_RECIPIENTS: usize = 1000;
+/// Idle time after which a session is closed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+/// Failed commands after which a session is closed.
+const MAX_ERRORS: u32 = 20;
+
+/// An SMTP reply with its enhanced status code (RFC 3463).
+#[derive(Debug, Clone)]
+pub(crate) struct Reply {
+    code: u16,
+    status: &'static str,
+    text: Cow<'static, str>,
+}
+
+impl Reply {
+    pub(crate) const QUEUED: Reply = Reply::new(250, "2.0.0", "Message accepted for delivery");
+    pub(crate) const SHUTTING_DOWN: Reply =
+        Reply::new(421, "4.3.2", "Service shutting down, try again later");
+
+    const fn new(code: u16, status: &'static str, text: &'static str) -> Self {
+        Self {
+            code,
+            status,
+            text: Cow::Borrowed(text),
+        }
+    }
+
+    fn with_text(code: u16, status: &'static str, text: String) -> Self {
+        Self {
+            code,
+            status,
+            text: Cow::Owned(text),
+        }
+    }
+
+    /// The reply to a message the API or validation rejected.
+    ///
+    /// Transient failures get a `4xx` reply so the client retries later; invalid
+    /// messages get a permanent `5xx` reply.
+    pub(crate) fn from_error(err: &LanefulError) -> Self {
+        let text = err.to_string().replace(['\r', '\n'], " ");
+        match err {
+            LanefulError::ValidationError(_) | LanefulError::SerializationError(_) => {
+                Self::with_text(554, "5.6.0", text)
+            }
+            _ if err.status() == Some(429) => Self::with_text(451, "4.7.0", text),
+            _ if err.is_retryable() || matches!(err, LanefulError::CircuitOpen) => {
+                Self::with_text(451, "4.4.0", text)
+            }
+            // The relay's own credentials or configuration are wrong: keep the message
+            // queued at the client until that is fixed.
+            _ if matches!(err.status(), Some(401 | 403)) => Self::with_text(451, "4.7.1", text),
+            LanefulError::ApiStatusError { .. } => Self::with_text(554, "5.7.0", text),
+            _ => Self::with_text(451, "4.3.0", text),
+        }
+    }
+
+    /// The reply to a message the API accepted the request for, which may still have
+    /// rejected the message itself.
+    fn from_response(response: &SendEmailResponse) -> Self {
+        if response.status != SendStatus::Rejected {
+            return Self::QUEUED;
+        }
+        let reason = response
+            .results
+            .first()
+            .and_then(|result| result.reason.as_deref())
+            .unwrap_or("Message rejected");
+        Self::with_text(554, "5.7.0", reason.replace(['\r', '\n'], " "))
+    }
+
+    fn is_success(&self) -> bool {
+        self.code < 400
+    }
+}
+
+/// Transaction state of a session.
+#[derive(Default)]
+struct Session {
+    greeted: bool,
+    secure: bool,
+    authenticated: bool,
+    sender: Option<Option<String>>,
+    recipients: Vec<String>,
+    errors: u32,
+}
+
+impl Session {
+    fn reset(&mut self) {
+        self.sender = None;
+        self.recipients.clear();
+    }
+}
+
+/// What a session loop ended with.
+enum End<S> {
+    Closed,
+    StartTls(S),
+}
+
+/// Serve one connection.
+pub(crate) async fn run<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    relay: &Relay,
+) -> io::Result<()> {
+    let mut session = Session::default();
+    let mut reader = BufReader::new(stream);
+    reply(
+        &mut reader,
+        &Reply::with_text(220, "", format!("{} ESMTP Laneful relay", relay.hostname)),
+    )
+    .await?;
+
+    if let End::StartTls(stream) = serve(reader, relay, &mut session).await?
+        && let Some(acceptor) = &relay.tls
+    {
+        let stream = acceptor.accept(stream).await?;
+        // Forget everything learned before the handshake (RFC 3207, section 4.2).
+        session = Session {
+            secure: true,
+            ..Session::default()
+        };
+        serve(BufReader::new(stream), relay, &mut session).await?;
+    }
+    Ok(())
+}
+
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: BufReader<S>,
+    relay: &Relay,
+    session: &mut Session,
+) -> io::Result<End<S>> {
+    loop {
+        let Some(line) = read_line(&mut stream).await? else {
+            return Ok(End::Closed);
+        };
+        let (verb, args) = line
+            .split_once(' ')
+            .map_or((line.as_str(), ""), |(verb, args)| (verb, args.trim()));
+
+        let response = match verb.to_ascii_uppercase().as_str() {
+            "EHLO" => {
+                session.greeted = true;
+                session.reset();
+                write(&mut stream, &ehlo(relay, session)).await?;
+                continue;
+            }
+            "HELO" => {
+                session.greeted = true;
+                session.reset();
+                Reply::with_text(250, "", relay.hostname.clone())
+            }
+            "STARTTLS" => {
+                if relay.tls.is_none() {
+                    Reply::new(502, "5.5.1", "STARTTLS not available")
+                } else if session.secure {
+                    Reply::new(503, "5.5.1", "TLS already active")
+                } else {
+                    reply(&mut stream, &Reply::new(220, "2.0.0", "Ready to start TLS")).await?;
+                    // Commands pipelined before the handshake must be discarded.
+                    return Ok(End::StartTls(stream.into_inner()));
+                }
+            }
+            "AUTH" => auth(&mut stream, relay, session, args).await?,
+            "MAIL" => mail(relay, session, args),
+            "RCPT" => rcpt(session, args),
+            "DATA" => {
+                if session.recipients.is_empty() {
+                    Reply::new(503, "5.5.1", "Need RCPT command")
+                } else {
+                    reply(
+                        &mut stream,
+                        &Reply::new(354, "", "End data with <CR><LF>.<CR><LF>"),
+                    )
+                    .await?;
+                    let response = data(&mut stream, relay, session).await?;
+                    session.reset();
+                    response
+                }
+            }
+            "RSET" => {
+                session.reset();
+                Reply::new(250, "2.0.0", "OK")
+            }
+            "NOOP" => Reply::new(250, "2.0.0", "OK"),
+            "VRFY" => Reply::new(252, "2.5.0", "Cannot verify user, will attempt delivery"),
+            "HELP" => Reply::new(214, "2.0.0", "See RFC 5321"),
+            "QUIT" => {
+                reply(&mut stream, &Reply::new(221, "2.0.0", "Bye")).await?;
+                return Ok(End::Closed);
+            }
+            _ => Reply::new(500, "5.5.2", "Command not recognized"),
+        };
+
+        if response.code >= 500 {
+            session.errors += 1;
+            if session.errors >= MAX_ERRORS {
+                reply(&mut stream, &Reply::new(421, "4.7.0", "Too many errors")).await?;
+                return Ok(End::Closed);
+            }
+        }
+        reply(&mut stream, &response).await?;
+    }
+}
+
+/// Whether AUTH is offered: only over TLS when STARTTLS is available.
+fn auth_offered(relay: &Relay, session: &Session) -> bool {
+    relay.credentials.is_some() && (session.secure || relay.tls.is_none())
+}
+
+fn ehlo(relay: &Relay, session: &Session) -> String {
+    let mut lines = vec![
+        relay.hostname.clone(),
+        "PIPELINING".into(),
+        format!("SIZE {}", relay.max_message_size),
+        "8BITMIME".into(),
+        "SMTPUTF8".into(),
+        "ENHANCEDSTATUSCODES".into(),
+    ];
+    if relay.tls.is_some() && !session.secure {
+        lines.push("STARTTLS".into());
+    }
+    if auth_offered(relay, session) {
+        lines.push("AUTH PLAIN LOGIN".into());
+    }
+    let last = lines.len() - 1;
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| format!("250{}{line}\r\n", if i == last { ' ' } else { '-' }))
+        .collect()
+}
+
+async fn auth<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut BufReader<S>,
+    relay: &Relay,
+    session: &mut Session,
+    args: &str,
+) -> io::Result<Reply> {
+    let Some((username, password)) = &relay.credentials else {
+        return Ok(Reply::new(502, "5.5.1", "AUTH not available"));
+    };
+    if !auth_offered(relay, session) {
+        return Ok(Reply::new(
+            538,
+            "5.7.11",
+            "Encryption required, use STARTTLS",
+        ));
+    }
+    if session.authenticated {
+        return Ok(Reply::new(503, "5.5.1", "Already authenticated"));
+    }
+    if session.sender.is_some() {
+        return Ok(Reply::new(
+            503,
+            "5.5.1",
+            "AUTH not allowed during a transaction",
+        ));
+    }
+
+    let (mechanism, initial) = args
+        .split_once(' ')
+        .map_or((args, None), |(mechanism, initial)| {
+            (mechanism, Some(initial.trim()))
+        });
+    let given = match mechanism.to_ascii_uppercase().as_str() {
+        "PLAIN" => {
+            let response = match initial {
+                Some(initial) => initial.to_string(),
+                None => match challenge(stream, "").await? {
+                    Some(response) => response,
+                    None => return Ok(cancelled()),
+                },
+            };
+            // authzid NUL authcid NUL passwd
+            let Some(decoded) = decode(&response) else {
+                return Ok(malformed());
+            };
+            let mut fields = decoded.splitn(3, |&b| b == 0).skip(1);
+            match (fields.next(), fields.next()) {
+                (Some(user), Some(pass)) => (user.to_vec(), pass.to_vec()),
+                _ => return Ok(malformed()),
+            }
+        }
+        "LOGIN" => {
+            let user = match initial {
+                Some(initial) => initial.to_string(),
+                None => match challenge(stream, "VXNlcm5hbWU6").await? {
+                    Some(response) => response,
+                    None => return Ok(cancelled()),
+                },
+            };
+            let Some(pass) = challenge(stream, "UGFzc3dvcmQ6").await? else {
+                return Ok(cancelled());
+            };
+            match (decode(&user), decode(&pass)) {
+                (Some(user), Some(pass)) => (user, pass),
+                _ => return Ok(malformed()),
+            }
+        }
+        _ => {
+            return Ok(Reply::new(
+                504,
+                "5.5.4",
+                "Unrecognized authentication mechanism",
+            ));
+        }
+    };
+
+    let valid = given.0.ct_eq(username.as_bytes()) & given.1.ct_eq(password.as_bytes());
+    if bool::from(valid) {
+        session.authenticated = true;
+        Ok(Reply::new(235, "2.7.0", "Authentication successful"))
+    } else {
+        Ok(Reply::new(
+            535,
+            "5.7.8",
+            "Authentication credentials invalid",
+        ))
+    }
+}
+
+/// Send a `334` challenge and read the response; `None` if the client cancelled.
+async fn challenge<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut BufReader<S>,
+    prompt: &str,
+) -> io::Result<Option<String>> {
+    write(stream, &format!("334 {prompt}\r\n")).await?;
+    let response = read_line(stream).await?.unwrap_or_default();
+    Ok((response != "*").then_some(response))
+}
+
+fn decode(value: &str) -> Option<Vec<u8>> {
+    BASE64.decode(value.trim()).ok()
+}
+
+fn cancelled() -> Reply {
+    Reply::new(501, "5.0.0", "Authentication cancelled")
+}
+
+fn malformed() -> Reply {
+    Reply::new(501, "5.5.2", "Malformed authentication response")
+}
+
+fn mail(relay: &Relay, session: &mut Session, args: &str) -> Reply {
+    if !session.greeted {
+        return Reply::new(503, "5.5.1", "Send EHLO first");
+    }
+    if relay.credentials.is_some() && !session.authenticated {
+        return Reply::new(530, "5.7.0", "Authentication required");
+    }
+    if session.sender.is_some() {
+        return Reply::new(503, "5.5.1", "Sender already specified");
+    }
+    let Some((path, params)) = path(args, "FROM:") else {
+        return Reply::new(501, "5.5.4", "Syntax: MAIL FROM:<address>");
+    };
+    for param in params.split_whitespace() {
+        if let Some(size) = param.to_ascii_uppercase().strip_prefix("SIZE=")
+            && size
+                .parse::<usize>()
+                .is_ok_and(|size| size > relay.max_message_size)
+        {
+            return Reply::new(
+                552,
+                "5.3.4",
+                "Message size exceeds fixed maximum message size",
+            );
+        }
+    }
+    session.sender = Some((!path.is_empty()).then_some(path));
+    Reply::new(250, "2.1.0", "OK")
+}
+
+fn rcpt(session: &mut Session, args: &str) -> Reply {
+    if session.sender.is_none() {
+        return Reply::new(503, "5.5.1", "Need MAIL command");
+    }
+    let Some((path, _)) = path(args, "TO:") else {
+        return Reply::new(501, "5.5.4", "Syntax: RCPT TO:<address>");
+    };
+    if path.is_empty() || !path.contains('@') {
+        return Reply::new(553, "5.1.3", "Invalid recipient address");
+    }
+    if session.recipients.len() >= MAX_RECIPIENTS {
+        return Reply::new(452, "4.5.3", "Too many recipients");
+    }
+    session.recipients.push(path);
+    Reply::new(250, "2.1.5", "OK")
+}
+
+/// Parse `FROM:<path> params` or `TO:<path> params`.
+fn path(args: &str, prefix: &str) -> Option<(String, String)> {
+    let head = args.get(..prefix.len())?;
+    if !head.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let rest = args[prefix.len()..].trim_start();
+    let rest = rest.strip_prefix('<')?;
+    let (path, params) = rest.split_once('>')?;
+    // Drop a source route (`@a,@b:user@example.com`), obsolete but allowed.
+    let path = path.rsplit_once(':').map_or(path, |(_, path)| path);
+    Some((path.to_string(), params.trim().to_string()))
+}
+
+/// Read the message after `DATA` and hand it to the queue.
+async fn data<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut BufReader<S>,
+    relay: &Relay,
+    session: &Session,
+) -> io::Result<Reply> {
+    let mut message = Vec::new();
+    let mut too_large = false;
+    let mut line = Vec::new();
+    // Long lines are read in chunks of at most `MAX_LINE` bytes.
+    let mut line_start = true;
+    loop {
+        line.clear();
+        let read = tokio::time::timeout(
+            COMMAND_TIMEOUT,
+            (&mut *stream).take(MAX_LINE).read_until(b'\n', &mut line),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out reading message"))??;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if line_start && (line == b".\r\n" || line == b".\n") {
+            break;
+        }
+        // Undo dot-stuffing.
+        let content = if line_start {
+            line.strip_prefix(b".").unwrap_or(&line)
+        } else {
+            &line
+        };
+        line_start = line.ends_with(b"\n");
+        // Keep reading up to the end of the message, but stop storing it.
+        if too_large || message.len() + content.len() > relay.max_message_size {
+            too_large = true;
+            continue;
+        }
+        message.extend_from_slice(content);
+    }
+
+    if too_large {
+        return Ok(Reply::new(
+            552,
+            "5.3.4",
+            "Message size exceeds fixed maximum message size",
+        ));
+    }
+    let sender = session.sender.clone().flatten();
+    let email =
+        match Email::from_mime_with_envelope(&message, sender.as_deref(), &session.recipients)
+            .and_then(|import| import.email.validate().map(|()| import.email))
+        {
+            Ok(email) => email,
+            Err(err) => return Ok(Reply::from_error(&err)),
+        };
+
+    let response = match relay.queue.send(email).await {
+        Ok(response) => Reply::from_response(&response),
+        Err(err) if matches!(*err, LanefulError::Cancelled) => Reply::SHUTTING_DOWN,
+        Err(err) => Reply::from_error(&err),
+    };
+    if !response.is_success() {
+        eprintln!("message from {sender:?} rejected: {}", response.text);
+    }
+    Ok(response)
+}
+
+/// Read a command line without its line ending; `None` at end of stream.
+async fn read_line<S: AsyncRead + Unpin>(stream: &mut BufReader<S>) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = tokio::time::timeout(
+        COMMAND_TIMEOUT,
+        (&mut *stream).take(MAX_LINE).read_until(b'\n', &mut line),
+    )
+    .await
+    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out waiting for a command"))??;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "command line too long",
+        ));
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+async fn reply<S: AsyncWrite + Unpin>(stream: &mut S, reply: &Reply) -> io::Result<()> {
+    let status = if reply.status.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", reply.status)
+    };
+    write(
+        stream,
+        &format!("{} {status}{}\r\n", reply.code, reply.text),
+    )
+    .await
+}
+
+async fn write<S: AsyncWrite + Unpin>(stream: &mut S, text: &str) -> io::Result<()> {
+    stream.write_all(text.as_bytes()).await?;
+    stream.flush().await
+}
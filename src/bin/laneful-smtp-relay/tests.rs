@@ -0,0 +1,333 @@
+//! Sessions driven over an in-memory stream against a local fake of the API.
+
+use crate::{Relay, session};
+use axum::{Json, Router, http::StatusCode, routing::post};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use laneful_rs::{EmailQueue, LanefulClient};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf};
+
+/// Emails the fake API received.
+type Received = Arc<Mutex<Vec<Value>>>;
+
+/// Answer like the API: `400` for subjects containing "bad", `503` for "down", and a
+/// rejected result for "reject".
+fn answer(email: &Value) -> (StatusCode, Json<Value>) {
+    let subject = email["subject"].as_str().unwrap_or_default();
+    if subject.contains("bad") {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid email" })),
+        )
+    } else if subject.contains("down") {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "unavailable" })),
+        )
+    } else if subject.contains("reject") {
+        (
+            StatusCode::OK,
+            Json(json!({
+                "status": "rejected",
+                "results": [{ "index": 0, "status": "rejected", "reason": "recipient\r\nsuppressed" }],
+            })),
+        )
+    } else {
+        (StatusCode::OK, Json(json!({ "status": "accepted" })))
+    }
+}
+
+async fn relay(
+    credentials: Option<(&str, &str)>,
+    max_message_size: usize,
+) -> (Arc<Relay>, Received) {
+    let received = Received::default();
+    let app = Router::new().route(
+        "/v1/email/send",
+        post({
+            let received = received.clone();
+            move |Json(body): Json<Value>| async move {
+                let email = body["emails"][0].clone();
+                received.lock().unwrap().push(email.clone());
+                answer(&email)
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = LanefulClient::new(format!("http://{addr}"), "test-key").unwrap();
+    let queue = EmailQueue::builder(client)
+        .max_batch_size(1)
+        .max_wait(Duration::from_millis(10))
+        .build();
+    let relay = Relay {
+        hostname: "relay.test".into(),
+        credentials: credentials.map(|(user, pass)| (user.into(), pass.into())),
+        tls: None,
+        max_message_size,
+        queue,
+    };
+    (Arc::new(relay), received)
+}
+
+/// The client end of a session.
+struct Client {
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+}
+
+impl Client {
+    async fn connect(relay: &Arc<Relay>) -> Self {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let relay = relay.clone();
+        tokio::spawn(async move { session::run(server, &relay).await });
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = Client {
+            reader: BufReader::new(reader),
+            writer,
+        };
+        assert!(client.reply().await.starts_with("220 relay.test"));
+        client
+    }
+
+    /// Read a reply, returning its last line.
+    async fn reply(&mut self) -> String {
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).await.unwrap();
+            assert!(line.ends_with("\r\n"), "truncated reply {line:?}");
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return line.trim_end().to_string();
+            }
+        }
+    }
+
+    async fn send(&mut self, data: &str) {
+        self.writer.write_all(data.as_bytes()).await.unwrap();
+    }
+
+    async fn command(&mut self, line: &str) -> String {
+        self.send(&format!("{line}\r\n")).await;
+        self.reply().await
+    }
+
+    /// Send a command and check the start of its reply.
+    async fn expect(&mut self, line: &str, reply: &str) {
+        let got = self.command(line).await;
+        assert!(
+            got.starts_with(reply),
+            "{line:?}: expected {reply:?}, got {got:?}"
+        );
+    }
+
+    /// Send a whole transaction, returning the reply to the message.
+    async fn deliver(&mut self, subject: &str, body: &str) -> String {
+        self.expect("MAIL FROM:<sender@example.com>", "250").await;
+        self.expect("RCPT TO:<recipient@example.com>", "250").await;
+        self.expect("DATA", "354").await;
+        self.send(&message(subject, body)).await;
+        self.reply().await
+    }
+}
+
+/// A message in SMTP form: CRLF line endings and the terminating dot.
+fn message(subject: &str, body: &str) -> String {
+    format!(
+        "From: sender@example.com\r\nTo: recipient@example.com\r\nSubject: {subject}\r\n\r\n{body}\r\n.\r\n"
+    )
+}
+
+#[tokio::test]
+async fn paths_accept_source_routes_and_reject_bad_syntax() {
+    let (relay, received) = relay(None, 1024 * 1024).await;
+    let mut client = Client::connect(&relay).await;
+
+    client
+        .expect("MAIL FROM:<sender@example.com>", "503 5.5.1")
+        .await;
+    client
+        .expect("EHLO client.test", "250 ENHANCEDSTATUSCODES")
+        .await;
+    client
+        .expect("RCPT TO:<recipient@example.com>", "503 5.5.1")
+        .await;
+    client
+        .expect("MAIL FROM:sender@example.com", "501 5.5.4")
+        .await;
+    client
+        .expect("MAIL TO:<sender@example.com>", "501 5.5.4")
+        .await;
+    client
+        .expect(
+            "mail from:<@a.example,@b.example:envelope@example.com> SIZE=100",
+            "250 2.1.0",
+        )
+        .await;
+    client.expect("RCPT TO:<>", "553 5.1.3").await;
+    client.expect("RCPT TO:<recipient>", "553 5.1.3").await;
+    client
+        .expect("RCPT TO:<recipient@example.com", "501 5.5.4")
+        .await;
+    client
+        .expect("rcpt to: <recipient@example.com>", "250 2.1.5")
+        .await;
+    client.expect("DATA", "354").await;
+    client.send(&message("Hello", "Hi")).await;
+    assert!(client.reply().await.starts_with("250 2.0.0"));
+
+    // The null reverse-path is allowed.
+    client.expect("MAIL FROM:<>", "250 2.1.0").await;
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0]["from"]["email"], "envelope@example.com");
+    assert_eq!(received[0]["to"][0]["email"], "recipient@example.com");
+}
+
+#[tokio::test]
+async fn auth_plain_checks_credentials() {
+    let (relay, received) = relay(Some(("user", "secret")), 1024 * 1024).await;
+    let mut client = Client::connect(&relay).await;
+    client
+        .expect("EHLO client.test", "250 AUTH PLAIN LOGIN")
+        .await;
+    client
+        .expect("MAIL FROM:<sender@example.com>", "530 5.7.0")
+        .await;
+
+    let wrong = BASE64.encode("\0user\0wrong");
+    client
+        .expect(&format!("AUTH PLAIN {wrong}"), "535 5.7.8")
+        .await;
+    client.expect("AUTH PLAIN !!!", "501 5.5.2").await;
+    let no_password = BASE64.encode("user");
+    client
+        .expect(&format!("AUTH PLAIN {no_password}"), "501 5.5.2")
+        .await;
+    client.expect("AUTH CRAM-MD5", "504 5.5.4").await;
+
+    // Without an initial response, the credentials follow an empty challenge.
+    assert_eq!(client.command("AUTH PLAIN").await, "334");
+    client.expect("*", "501 5.0.0").await;
+    assert_eq!(client.command("AUTH PLAIN").await, "334");
+    let valid = BASE64.encode("admin\0user\0secret");
+    client.expect(&valid, "235 2.7.0").await;
+    client.expect("AUTH PLAIN", "503 5.5.1").await;
+
+    assert!(client.deliver("Hello", "Hi").await.starts_with("250 2.0.0"));
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn auth_login_checks_credentials() {
+    let (relay, _) = relay(Some(("user", "secret")), 1024 * 1024).await;
+    let mut client = Client::connect(&relay).await;
+    client.command("EHLO client.test").await;
+
+    assert_eq!(client.command("AUTH LOGIN").await, "334 VXNlcm5hbWU6");
+    assert_eq!(
+        client.command(&BASE64.encode("user")).await,
+        "334 UGFzc3dvcmQ6"
+    );
+    client.expect(&BASE64.encode("wrong"), "535 5.7.8").await;
+
+    assert_eq!(client.command("AUTH LOGIN").await, "334 VXNlcm5hbWU6");
+    client.expect("*", "501 5.0.0").await;
+
+    let user = BASE64.encode("user");
+    assert_eq!(
+        client.command(&format!("AUTH LOGIN {user}")).await,
+        "334 UGFzc3dvcmQ6"
+    );
+    client.expect("not base64!", "501 5.5.2").await;
+
+    assert_eq!(
+        client.command(&format!("AUTH LOGIN {user}")).await,
+        "334 UGFzc3dvcmQ6"
+    );
+    client.expect(&BASE64.encode("secret"), "235 2.7.0").await;
+    client
+        .expect("MAIL FROM:<sender@example.com>", "250 2.1.0")
+        .await;
+}
+
+#[tokio::test]
+async fn dot_stuffing_is_undone_only_at_line_starts() {
+    let (relay, received) = relay(None, 1024 * 1024).await;
+    let mut client = Client::connect(&relay).await;
+    client.command("EHLO client.test").await;
+
+    // Lines longer than `MAX_LINE` are read in chunks; a chunk starting with a dot
+    // in the middle of a line is neither unstuffed nor the end of the message.
+    let long = "x".repeat(4096);
+    let body = format!("..first\r\n{long}..middle\r\n{long}.\r\n..\r\nlast");
+    assert!(client.deliver("Dots", &body).await.starts_with("250 2.0.0"));
+
+    let received = received.lock().unwrap();
+    let text = received[0]["text_content"]
+        .as_str()
+        .unwrap()
+        .replace("\r\n", "\n");
+    assert_eq!(
+        text.trim_end(),
+        format!(".first\n{long}..middle\n{long}.\n.\nlast")
+    );
+}
+
+#[tokio::test]
+async fn oversized_messages_are_refused() {
+    let (relay, received) = relay(None, 1024).await;
+    let mut client = Client::connect(&relay).await;
+    client
+        .expect("EHLO client.test", "250 ENHANCEDSTATUSCODES")
+        .await;
+
+    client
+        .expect("MAIL FROM:<sender@example.com> SIZE=1025", "552 5.3.4")
+        .await;
+    // The declared size is only a hint: the message itself is measured too.
+    assert!(
+        client
+            .deliver("Big", &"y".repeat(2000))
+            .await
+            .starts_with("552 5.3.4")
+    );
+    // The session is still usable after the message was read to its end.
+    client.expect("NOOP", "250 2.0.0").await;
+    assert!(client.deliver("Small", "Hi").await.starts_with("250 2.0.0"));
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0]["subject"], "Small");
+}
+
+#[tokio::test]
+async fn api_outcomes_map_to_replies() {
+    let (relay, _) = relay(None, 1024 * 1024).await;
+    let mut client = Client::connect(&relay).await;
+    client.command("EHLO client.test").await;
+
+    // Permanent failures get a 5xx reply, transient ones a 4xx reply.
+    assert!(client.deliver("bad", "Hi").await.starts_with("554 5.7.0"));
+    assert!(client.deliver("down", "Hi").await.starts_with("451 4.4.0"));
+    assert_eq!(
+        client.deliver("reject", "Hi").await,
+        "554 5.7.0 recipient  suppressed"
+    );
+    assert!(client.deliver("Hello", "Hi").await.starts_with("250 2.0.0"));
+
+    // A message the client-side validation refuses never reaches the API.
+    client.expect("MAIL FROM:<sender@example.com>", "250").await;
+    client
+        .expect("RCPT TO:<recipient@example.com>", "250")
+        .await;
+    client.expect("DATA", "354").await;
+    client.send("Subject: No body\r\n\r\n.\r\n").await;
+    assert!(client.reply().await.starts_with("554 5.6.0"));
+    assert_eq!(client.command("QUIT").await, "221 2.0.0 Bye");
+}
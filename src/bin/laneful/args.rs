@@ -0,0 +1,93 @@
+//! Command-line argument parsing.
+
+use crate::Error;
+
+/// Options, switches and positional arguments of a command.
+#[derive(Debug, Default)]
+pub(crate) struct Args {
+    options: Vec<(String, String)>,
+    switches: Vec<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Parse `args`, where the names in `options` take a value (`--name value` or
+    /// `--name=value`) and those in `switches` do not. Everything after `--` is
+    /// positional.
+    pub(crate) fn parse(
+        args: impl IntoIterator<Item = String>,
+        options: &[&str],
+        switches: &[&str],
+    ) -> Result<Self, Error> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positional.extend(args.by_ref());
+                break;
+            }
+            let Some(flag) = arg.strip_prefix("--").filter(|_| arg.len() > 2) else {
+                if arg == "-h" {
+                    parsed.switches.push("help".into());
+                } else {
+                    parsed.positional.push(arg);
+                }
+                continue;
+            };
+
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            if options.contains(&name) {
+                let value = match inline {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or_else(|| Error::Usage(format!("--{name} needs a value")))?,
+                };
+                parsed.options.push((name.to_string(), value));
+            } else if switches.contains(&name) || name == "help" {
+                if inline.is_some() {
+                    return Err(Error::Usage(format!("--{name} does not take a value")));
+                }
+                parsed.switches.push(name.to_string());
+            } else {
+                return Err(Error::Usage(format!("unknown option --{name}")));
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The last value given for an option.
+    pub(crate) fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// All values given for a repeatable option, in order.
+    pub(crate) fn values(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.options
+            .iter()
+            .filter(move |(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether a switch was given.
+    pub(crate) fn has(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    /// Names of the options given, in order.
+    pub(crate) fn option_names(&self) -> impl Iterator<Item = &str> {
+        self.options.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Positional arguments.
+    pub(crate) fn positional(&self) -> &[String] {
+        &self.positional
+    }
+}
@@ -0,0 +1,169 @@
+//! Emails from command-line options and JSON files.
+
+use crate::Error;
+use crate::args::Args;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use laneful_rs::{Attachment, Email, LanefulClient, SendEmailRequest};
+use std::io::Read;
+use std::path::Path;
+
+/// Build an email from the options of `laneful send`, starting from the client's
+/// defaults (or those of `--profile`).
+pub(crate) fn build(client: &LanefulClient, args: &Args) -> Result<Email, Error> {
+    let mut builder = match args.value("profile") {
+        Some(profile) => client.profile_email(profile)?,
+        None => client.email(),
+    };
+
+    if let Some(from) = args.value("from") {
+        let (email, name) = address(from)?;
+        builder = builder.from(email, name.as_deref());
+    }
+    for to in args.values("to") {
+        let (email, name) = address(to)?;
+        builder = builder.to(email, name.as_deref());
+    }
+    for cc in args.values("cc") {
+        let (email, name) = address(cc)?;
+        builder = builder.cc(email, name.as_deref());
+    }
+    for bcc in args.values("bcc") {
+        let (email, name) = address(bcc)?;
+        builder = builder.bcc(email, name.as_deref());
+    }
+    if let Some(reply_to) = args.value("reply-to") {
+        let (email, name) = address(reply_to)?;
+        builder = builder.reply_to(email, name.as_deref());
+    }
+    if let Some(subject) = args.value("subject") {
+        builder = builder.subject(subject);
+    }
+    if let Some(text) = content(args, "text")? {
+        builder = builder.text_content(text);
+    }
+    if let Some(html) = content(args, "html")? {
+        builder = builder.html_content(html);
+    }
+    for path in args.values("attach") {
+        builder = builder.attachment(attachment(path)?);
+    }
+    for header in args.values("header") {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| Error::Usage(format!("--header {header:?} is not 'NAME: VALUE'")))?;
+        builder = builder.header(name.trim(), value.trim());
+    }
+    if let Some(tag) = args.value("tag") {
+        builder = builder.tag(tag);
+    }
+    if let Some(id) = args.value("template-id") {
+        builder = builder.template_id(id);
+    }
+    if let Some(data) = args.value("template-data") {
+        let data = serde_json::from_str(data)
+            .map_err(|err| Error::Usage(format!("--template-data is not valid JSON: {err}")))?;
+        builder = builder.template_data(data);
+    }
+    if let Some(time) = args.value("send-time") {
+        let time = time.parse().map_err(|_| {
+            Error::Usage(format!(
+                "--send-time must be a Unix timestamp, got {time:?}"
+            ))
+        })?;
+        builder = builder.send_time(time);
+    }
+    Ok(builder.build()?)
+}
+
+/// Read the emails of a request body, a single email or an array of emails.
+pub(crate) fn read_emails(path: &str) -> Result<Vec<Email>, Error> {
+    let invalid = |err: serde_json::Error| Error::Input(format!("{path}: {err}"));
+    let value: serde_json::Value = serde_json::from_slice(&read(path)?).map_err(invalid)?;
+    match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value).map_err(invalid),
+        serde_json::Value::Object(ref object) if object.contains_key("emails") => {
+            let request: SendEmailRequest = serde_json::from_value(value).map_err(invalid)?;
+            Ok(request.emails)
+        }
+        _ => Ok(vec![serde_json::from_value(value).map_err(invalid)?]),
+    }
+}
+
+/// Read a file, or stdin for `-`.
+fn read(path: &str) -> Result<Vec<u8>, Error> {
+    let result = if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        std::fs::read(path)
+    };
+    result.map_err(|err| Error::Input(format!("{path}: {err}")))
+}
+
+/// Split `Name <email>` into the address and display name.
+fn address(value: &str) -> Result<(String, Option<String>), Error> {
+    let value = value.trim();
+    let Some((name, rest)) = value.split_once('<') else {
+        return Ok((value.to_string(), None));
+    };
+    let email = rest
+        .strip_suffix('>')
+        .filter(|email| !email.contains(['<', '>']))
+        .ok_or_else(|| Error::Usage(format!("invalid address {value:?}")))?;
+    let name = name.trim().trim_matches('"').trim();
+    Ok((
+        email.trim().to_string(),
+        (!name.is_empty()).then(|| name.to_string()),
+    ))
+}
+
+/// A body given either inline (`--text`) or as a file (`--text-file`).
+fn content(args: &Args, option: &str) -> Result<Option<String>, Error> {
+    let file_option = format!("{option}-file");
+    match (args.value(option), args.value(&file_option)) {
+        (Some(_), Some(_)) => Err(Error::Usage(format!(
+            "--{option} and --{file_option} cannot be combined"
+        ))),
+        (Some(content), None) => Ok(Some(content.to_string())),
+        (None, Some(path)) => String::from_utf8(read(path)?)
+            .map(Some)
+            .map_err(|_| Error::Input(format!("{path}: not valid UTF-8"))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// An attachment read from a file, with its content type guessed from the extension.
+fn attachment(path: &str) -> Result<Attachment, Error> {
+    let content = read(path)?;
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .map_or_else(|| "attachment".into(), |name| name.to_string_lossy());
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    let content_type = match extension.as_deref() {
+        Some("txt" | "log") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("ics") => "text/calendar",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("eml") => "message/rfc822",
+        _ => "application/octet-stream",
+    };
+    Ok(Attachment::new(
+        file_name,
+        BASE64.encode(content),
+        content_type,
+    ))
+}
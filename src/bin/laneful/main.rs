@@ -0,0 +1,298 @@
+//! `laneful` command-line tool: send test and incident emails, validate request files and
+//! verify webhook signatures without writing Rust.
+//!
+//! The client is configured like any other (`LANEFUL_CONFIG`, `LANEFUL_ENDPOINT`,
+//! `LANEFUL_API_KEY`, ...; see `LanefulConfig`). Run `laneful --help` for the commands.
+
+mod args;
+mod email;
+
+use args::Args;
+use laneful_rs::{LanefulClient, LanefulError, SendOptions, WebhookVerifier};
+use std::fmt;
+use std::io::Read;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: laneful <command> [options]
+
+Commands:
+  send             Send an email built from options, or a JSON request with --json
+  validate FILE... Check the emails in JSON requests without sending them
+  webhook verify   Check the signature of a webhook body read from stdin
+
+The client is configured with LANEFUL_CONFIG, LANEFUL_ENDPOINT, LANEFUL_API_KEY, etc.
+Run `laneful <command> --help` for the options of a command.";
+
+const SEND_USAGE: &str = "\
+Usage: laneful send --from ADDRESS --to ADDRESS --subject TEXT [options]
+       laneful send --json FILE [options]
+
+Addresses are `email` or `Name <email>`. FILE may be `-` to read stdin.
+
+Email options:
+  --from ADDRESS                  sender (default: from the configuration)
+  --to, --cc, --bcc ADDRESS       recipients; repeatable
+  --reply-to ADDRESS
+  --subject TEXT
+  --text TEXT, --text-file FILE   plain text body
+  --html TEXT, --html-file FILE   HTML body
+  --attach FILE                   attachment; repeatable
+  --header 'NAME: VALUE'          custom header; repeatable
+  --tag TAG
+  --template-id ID
+  --template-data JSON
+  --send-time UNIX_TIME           schedule the email (up to 72 hours ahead)
+
+Request options:
+  --json FILE                     send a request body ({\"emails\": [...]}), an email
+                                  or an array of emails instead of the options above
+  --profile NAME                  apply the defaults of a configured profile
+  --idempotency-key KEY
+  --dry-run                       print the request instead of sending it";
+
+const VALIDATE_USAGE: &str = "\
+Usage: laneful validate FILE...
+
+Checks that each file holds a request body ({\"emails\": [...]}), an email or an array
+of emails, and that every email passes the same validation as `EmailBuilder::build`.
+FILE may be `-` to read stdin. Exits with status 1 if any email is invalid.";
+
+const WEBHOOK_USAGE: &str = "\
+Usage: laneful webhook verify --signature SIGNATURE [--secret SECRET] < body
+
+Checks the X-Webhook-Signature of a webhook body read, byte for byte, from stdin.
+The secret defaults to LANEFUL_WEBHOOK_SECRET. Exits with status 1 if the signature
+does not match.";
+
+/// Why a command failed.
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// The command line is invalid.
+    Usage(String),
+    /// An input file could not be read or parsed.
+    Input(String),
+    /// The client or the API failed.
+    Laneful(LanefulError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) | Self::Input(message) => f.write_str(message),
+            Self::Laneful(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<LanefulError> for Error {
+    fn from(err: LanefulError) -> Self {
+        Self::Laneful(err)
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(Error::Usage(message)) => {
+            eprintln!("error: {message}\n\nRun `laneful --help` for usage.");
+            ExitCode::from(2)
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<ExitCode, Error> {
+    if args.is_empty() {
+        return Err(Error::Usage("no command given".into()));
+    }
+    let command = args.remove(0);
+    match command.as_str() {
+        "send" => send(args),
+        "validate" => validate(args),
+        "webhook" => webhook(args),
+        "help" | "--help" | "-h" => help(USAGE),
+        "--version" | "-V" => {
+            println!("laneful {}", env!("CARGO_PKG_VERSION"));
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(Error::Usage(format!("unknown command {command:?}"))),
+    }
+}
+
+fn help(usage: &str) -> Result<ExitCode, Error> {
+    println!("{usage}");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn send(args: Vec<String>) -> Result<ExitCode, Error> {
+    let args = Args::parse(
+        args,
+        &[
+            "from",
+            "to",
+            "cc",
+            "bcc",
+            "reply-to",
+            "subject",
+            "text",
+            "text-file",
+            "html",
+            "html-file",
+            "attach",
+            "header",
+            "tag",
+            "template-id",
+            "template-data",
+            "send-time",
+            "json",
+            "profile",
+            "idempotency-key",
+        ],
+        &["dry-run"],
+    )?;
+    if args.has("help") {
+        return help(SEND_USAGE);
+    }
+    if let Some(extra) = args.positional().first() {
+        return Err(Error::Usage(format!("unexpected argument {extra:?}")));
+    }
+
+    let client = LanefulClient::from_env()?;
+    let mut options = SendOptions::new();
+    if let Some(key) = args.value("idempotency-key") {
+        options = options.idempotency_key(key);
+    }
+
+    let emails = match args.value("json") {
+        Some(path) => {
+            if let Some(option) = args
+                .option_names()
+                .find(|name| !["json", "profile", "idempotency-key"].contains(name))
+            {
+                return Err(Error::Usage(format!(
+                    "--{option} cannot be combined with --json"
+                )));
+            }
+            if let Some(profile) = args.value("profile") {
+                options = options.profile(profile);
+            }
+            let emails = email::read_emails(path)?;
+            for (n, email) in emails.iter().enumerate() {
+                email
+                    .validate()
+                    .map_err(|err| Error::Input(format!("{path}: email {}: {err}", n + 1)))?;
+            }
+            emails
+        }
+        None => vec![email::build(&client, &args)?],
+    };
+
+    if args.has("dry-run") {
+        let report = client.send_dry_run_with_options(emails, &options)?;
+        for request in &report.requests {
+            println!("{}", request.body);
+        }
+        eprintln!(
+            "dry run: {} email(s) to {} recipient(s) in {} request(s), nothing sent",
+            report.email_count(),
+            report.recipient_count(),
+            report.requests.len()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let response = client.send_with_options(emails, &options)?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&response).map_err(LanefulError::from)?
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(args: Vec<String>) -> Result<ExitCode, Error> {
+    let args = Args::parse(args, &[], &[])?;
+    if args.has("help") {
+        return help(VALIDATE_USAGE);
+    }
+    if args.positional().is_empty() {
+        return Err(Error::Usage("validate needs at least one FILE".into()));
+    }
+
+    let mut valid = true;
+    for path in args.positional() {
+        let emails = match email::read_emails(path) {
+            Ok(emails) => emails,
+            Err(err) => {
+                println!("{err}");
+                valid = false;
+                continue;
+            }
+        };
+        if emails.is_empty() {
+            println!("{path}: no emails");
+            valid = false;
+            continue;
+        }
+
+        let mut errors = 0;
+        for (n, email) in emails.iter().enumerate() {
+            if let Err(err) = email.validate() {
+                println!("{path}: email {}: {err}", n + 1);
+                errors += 1;
+            }
+        }
+        if errors == 0 {
+            println!("{path}: {} email(s) valid", emails.len());
+        }
+        valid &= errors == 0;
+    }
+    Ok(if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn webhook(mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let subcommand = (!args.is_empty()).then(|| args.remove(0));
+    match subcommand.as_deref() {
+        Some("verify") => {}
+        Some("help" | "--help" | "-h") => return help(WEBHOOK_USAGE),
+        Some(other) => {
+            return Err(Error::Usage(format!("unknown webhook command {other:?}")));
+        }
+        None => return Err(Error::Usage("webhook needs a command: verify".into())),
+    }
+
+    let args = Args::parse(args, &["secret", "signature"], &[])?;
+    if args.has("help") {
+        return help(WEBHOOK_USAGE);
+    }
+    let signature = args
+        .value("signature")
+        .ok_or_else(|| Error::Usage("--signature is required".into()))?;
+    let secret = match args.value("secret") {
+        Some(secret) => secret.to_string(),
+        None => std::env::var("LANEFUL_WEBHOOK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| Error::Usage("--secret or LANEFUL_WEBHOOK_SECRET is required".into()))?,
+    };
+
+    let mut body = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut body)
+        .map_err(|err| Error::Input(format!("stdin: {err}")))?;
+
+    if WebhookVerifier::new(secret).verify(&body, signature.trim()) {
+        println!("valid signature");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("invalid signature");
+        Ok(ExitCode::FAILURE)
+    }
+}
@@ -1,5 +1,6 @@
 //! Builder pattern for constructing emails.
 
+use crate::defaults::EmailDefaults;
 use crate::error::{LanefulError, Result};
 use crate::models::{Attachment, Email, EmailAddress, Tracking};
 use std::collections::HashMap;
@@ -11,7 +12,7 @@ const MAX_WEBHOOK_DATA_KEY_LENGTH: usize = 50;
 const MAX_WEBHOOK_DATA_VALUE_LENGTH: usize = 100;
 
 /// Builder for constructing [`Email`] instances.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct EmailBuilder {
     from: Option<EmailAddress>,
     from_header: Option<EmailAddress>,
@@ -38,6 +39,19 @@ impl EmailBuilder {
         Self::default()
     }
 
+    /// Create a builder pre-filled with the given defaults.
+    pub(crate) fn with_defaults(defaults: &EmailDefaults) -> Self {
+        Self {
+            from: defaults.from.clone(),
+            from_header: defaults.from_header.clone(),
+            reply_to: defaults.reply_to.clone(),
+            headers: defaults.headers.clone(),
+            tag: defaults.tag.clone(),
+            tracking: defaults.tracking.clone(),
+            ..Self::default()
+        }
+    }
+
     /// Set the sender email address.
     pub fn from(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
         self.from = Some(match name {
@@ -166,64 +180,21 @@ impl EmailBuilder {
 
     /// Build the email.
     pub fn build(self) -> Result<Email> {
+        let email = self.assemble()?;
+        email.validate()?;
+        Ok(email)
+    }
+
+    /// Build the email, checking only that it has a sender and a subject.
+    pub(crate) fn assemble(self) -> Result<Email> {
         let from = self
             .from
             .ok_or_else(|| LanefulError::ValidationError("from address is required".into()))?;
 
-        let recipient_count = self.to.len() + self.cc.len() + self.bcc.len();
-
-        if recipient_count == 0 {
-            return Err(LanefulError::ValidationError(
-                "at least one recipient (to, cc, or bcc) is required".into(),
-            ));
-        }
-
-        if recipient_count > MAX_RECIPIENTS {
-            return Err(LanefulError::ValidationError(
-                "recipient limit exceeded (max 1000 across to/cc/bcc)".into(),
-            ));
-        }
-
         let subject = self
             .subject
             .ok_or_else(|| LanefulError::ValidationError("subject is required".into()))?;
 
-        if self.text_content.is_none() && self.html_content.is_none() && self.template_id.is_none()
-        {
-            return Err(LanefulError::ValidationError(
-                "either text_content, html_content, or template_id is required".into(),
-            ));
-        }
-
-        if let Some(tag) = &self.tag {
-            if tag.len() > MAX_TAG_LENGTH {
-                return Err(LanefulError::ValidationError(
-                    "tag length exceeds 100 characters".into(),
-                ));
-            }
-        }
-
-        if !self.webhook_data.is_empty() {
-            if self.webhook_data.len() > MAX_WEBHOOK_DATA_KEYS {
-                return Err(LanefulError::ValidationError(
-                    "webhook_data exceeds 10 keys".into(),
-                ));
-            }
-
-            for (key, value) in &self.webhook_data {
-                if key.len() > MAX_WEBHOOK_DATA_KEY_LENGTH {
-                    return Err(LanefulError::ValidationError(
-                        "webhook_data key length exceeds 50 characters".into(),
-                    ));
-                }
-                if value.len() > MAX_WEBHOOK_DATA_VALUE_LENGTH {
-                    return Err(LanefulError::ValidationError(
-                        "webhook_data value length exceeds 100 characters".into(),
-                    ));
-                }
-            }
-        }
-
         Ok(Email {
             from,
             from_header: self.from_header,
@@ -271,4 +242,83 @@ impl Email {
     pub fn builder() -> EmailBuilder {
         EmailBuilder::new()
     }
+
+    /// Check the email against the API's limits.
+    ///
+    /// [`EmailBuilder::build`] runs the same checks; use this for emails constructed or
+    /// deserialized directly.
+    pub fn validate(&self) -> Result<()> {
+        validate_recipient_count(
+            self.to.len()
+                + self.cc.as_ref().map_or(0, Vec::len)
+                + self.bcc.as_ref().map_or(0, Vec::len),
+        )?;
+        validate_content(self)?;
+        if let Some(webhook_data) = &self.webhook_data {
+            validate_webhook_data(webhook_data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the number of recipients of an email across `to`, `cc` and `bcc`.
+pub(crate) fn validate_recipient_count(recipient_count: usize) -> Result<()> {
+    if recipient_count == 0 {
+        return Err(LanefulError::ValidationError(
+            "at least one recipient (to, cc, or bcc) is required".into(),
+        ));
+    }
+
+    if recipient_count > MAX_RECIPIENTS {
+        return Err(LanefulError::ValidationError(
+            "recipient limit exceeded (max 1000 across to/cc/bcc)".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check the content and tag of an email.
+pub(crate) fn validate_content(email: &Email) -> Result<()> {
+    if email.text_content.is_none() && email.html_content.is_none() && email.template_id.is_none() {
+        return Err(LanefulError::ValidationError(
+            "either text_content, html_content, or template_id is required".into(),
+        ));
+    }
+
+    if email
+        .tag
+        .as_ref()
+        .is_some_and(|tag| tag.len() > MAX_TAG_LENGTH)
+    {
+        return Err(LanefulError::ValidationError(
+            "tag length exceeds 100 characters".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check the webhook data of an email.
+pub(crate) fn validate_webhook_data(webhook_data: &HashMap<String, String>) -> Result<()> {
+    if webhook_data.len() > MAX_WEBHOOK_DATA_KEYS {
+        return Err(LanefulError::ValidationError(
+            "webhook_data exceeds 10 keys".into(),
+        ));
+    }
+
+    for (key, value) in webhook_data {
+        if key.len() > MAX_WEBHOOK_DATA_KEY_LENGTH {
+            return Err(LanefulError::ValidationError(
+                "webhook_data key length exceeds 50 characters".into(),
+            ));
+        }
+        if value.len() > MAX_WEBHOOK_DATA_VALUE_LENGTH {
+            return Err(LanefulError::ValidationError(
+                "webhook_data value length exceeds 100 characters".into(),
+            ));
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,416 @@
+//! Concurrent sending of a stream of emails.
+
+use crate::client::{self, LanefulClient, SendOptions};
+use crate::error::{LanefulError, Result};
+use crate::models::{Email, EmailAddress, SendEmailResponse};
+use crate::rate_limit::Priority;
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc, watch};
+use tokio::time::Instant;
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_BATCH_WAIT: Duration = Duration::from_millis(100);
+
+/// State of a [`BulkSender`], changed through its [`BulkHandle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkState {
+    /// Emails are being sent.
+    Running,
+    /// No new requests are started; requests in flight complete.
+    Paused,
+    /// No new requests are started and the send ends once requests in flight complete.
+    Cancelled,
+}
+
+/// Pauses, resumes or cancels the sends of a [`BulkSender`].
+#[derive(Debug, Clone)]
+pub struct BulkHandle {
+    state: Arc<watch::Sender<BulkState>>,
+}
+
+impl BulkHandle {
+    /// Stop starting new requests until [`resume`](Self::resume) is called.
+    pub fn pause(&self) {
+        self.set(BulkState::Paused);
+    }
+
+    /// Resume a paused send.
+    pub fn resume(&self) {
+        self.set(BulkState::Running);
+    }
+
+    /// Stop the send: requests in flight complete and report their outcomes, emails
+    /// taken from the stream but not yet sent are reported as
+    /// [`LanefulError::Cancelled`], and the rest of the stream is not read. Cancelling is
+    /// final.
+    pub fn cancel(&self) {
+        self.state.send_replace(BulkState::Cancelled);
+    }
+
+    /// The current state.
+    pub fn state(&self) -> BulkState {
+        *self.state.borrow()
+    }
+
+    fn set(&self, state: BulkState) {
+        self.state.send_if_modified(|current| {
+            let changed = *current != BulkState::Cancelled && *current != state;
+            if changed {
+                *current = state;
+            }
+            changed
+        });
+    }
+}
+
+/// Outcome of one email sent by a [`BulkSender`].
+#[derive(Debug, Clone)]
+pub struct BulkOutcome {
+    /// Position of the email in the input stream, starting at 0.
+    pub index: usize,
+    /// The `to` recipients of the email.
+    pub recipients: Vec<EmailAddress>,
+    /// Response to the request carrying the email, or why it failed. The response is
+    /// narrowed to this email: its [`results`](SendEmailResponse::results) hold only this
+    /// email's result, if the API reported one, at index 0. Every email of a failed
+    /// request shares its error.
+    pub result: std::result::Result<SendEmailResponse, Arc<LanefulError>>,
+}
+
+/// Sends a stream of emails in batches, with several requests in flight.
+///
+/// Emails are read from the stream only as fast as requests complete: at most
+/// [`concurrency`](Self::concurrency) batches are in flight, and a consumer that does not
+/// read the outcomes eventually stops the reading of emails too. Requests go through
+/// the client as usual, so its rate limiter, retries and circuit breaker apply; batches
+/// use the [`Priority::Bulk`] lane by default so transactional mail sent through a
+/// shared limiter goes first.
+///
+/// Requires the `bulk` feature and a Tokio runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{BulkSender, Email, LanefulClient};
+///
+/// # async fn example(emails: impl futures_core::Stream<Item = Email> + Send + 'static) {
+/// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+/// let sender = BulkSender::new(client).concurrency(8);
+/// let handle = sender.handle();
+///
+/// let mut outcomes = sender.send(emails);
+/// while let Some(outcome) = outcomes.next().await {
+///     if let Err(err) = &outcome.result {
+///         eprintln!("email {} failed: {err}", outcome.index);
+///     }
+/// }
+/// # drop(handle);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BulkSender {
+    client: LanefulClient,
+    options: SendOptions,
+    batch_size: usize,
+    concurrency: usize,
+    max_batch_wait: Duration,
+    handle: BulkHandle,
+}
+
+impl BulkSender {
+    /// Create a sender sending through `client`.
+    pub fn new(client: LanefulClient) -> Self {
+        Self {
+            client,
+            options: SendOptions::new().priority(Priority::Bulk),
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+            max_batch_wait: DEFAULT_MAX_BATCH_WAIT,
+            handle: BulkHandle {
+                state: Arc::new(watch::Sender::new(BulkState::Running)),
+            },
+        }
+    }
+
+    /// Set the number of emails sent per request (default: 100).
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Set the number of requests in flight at once (default: 4).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.clamp(1, Semaphore::MAX_PERMITS);
+        self
+    }
+
+    /// Set how long to wait for a slow stream to fill a batch before sending it
+    /// (default: 100ms). A wait too long to represent waits for a full batch.
+    pub fn max_batch_wait(mut self, wait: Duration) -> Self {
+        self.max_batch_wait = wait;
+        self
+    }
+
+    /// Set the options used for every batch (default: the [`Priority::Bulk`] lane).
+    ///
+    /// An [idempotency key](SendOptions::idempotency_key) is extended for each batch with
+    /// the positions of its first and last emails in the stream, e.g. `key:emails-0-99`,
+    /// so that batches are not taken for repeats of one another.
+    pub fn send_options(mut self, options: SendOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// A handle pausing, resuming or cancelling the sends of this sender.
+    pub fn handle(&self) -> BulkHandle {
+        self.handle.clone()
+    }
+
+    /// Start sending `emails`, returning the stream of their outcomes.
+    ///
+    /// Outcomes arrive as requests complete, not in the order of the input. The stream
+    /// ends once every email read has an outcome. Dropping it cancels the send.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn send<S>(&self, emails: S) -> BulkOutcomes
+    where
+        S: Stream<Item = Email> + Send + 'static,
+    {
+        let buffer = self.batch_size.saturating_mul(self.concurrency);
+        let (outcomes, receiver) = mpsc::channel(buffer.min(Semaphore::MAX_PERMITS));
+        let driver = Driver {
+            client: self.client.clone(),
+            options: self.options.clone(),
+            batch_size: self.batch_size,
+            max_batch_wait: self.max_batch_wait,
+            permits: Arc::new(Semaphore::new(self.concurrency)),
+            state: self.handle.state.subscribe(),
+            outcomes,
+        };
+        tokio::spawn(driver.run(emails));
+        BulkOutcomes { receiver }
+    }
+}
+
+/// Stream of the outcomes of a [`BulkSender::send`].
+#[derive(Debug)]
+pub struct BulkOutcomes {
+    receiver: mpsc::Receiver<BulkOutcome>,
+}
+
+impl BulkOutcomes {
+    /// The next outcome, or `None` once the send has ended.
+    pub async fn next(&mut self) -> Option<BulkOutcome> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for BulkOutcomes {
+    type Item = BulkOutcome;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BulkOutcome>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Background task reading the stream and starting requests.
+struct Driver {
+    client: LanefulClient,
+    options: SendOptions,
+    batch_size: usize,
+    max_batch_wait: Duration,
+    permits: Arc<Semaphore>,
+    state: watch::Receiver<BulkState>,
+    outcomes: mpsc::Sender<BulkOutcome>,
+}
+
+impl Driver {
+    async fn run<S: Stream<Item = Email>>(mut self, emails: S) {
+        let mut emails = pin!(emails);
+        let mut index = 0;
+        let mut exhausted = false;
+
+        while !exhausted {
+            // Backpressure: read more emails only once a request slot is free.
+            let Some(permit) = self.permit().await else {
+                return;
+            };
+            let Some(first) = self.next(&mut emails).await else {
+                return;
+            };
+            let mut batch = vec![(index, first)];
+            index += 1;
+
+            let deadline = Instant::now().checked_add(self.max_batch_wait);
+            while batch.len() < self.batch_size {
+                let next = self.next(&mut emails);
+                let email = match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, next).await,
+                    None => Ok(next.await),
+                };
+                match email {
+                    Ok(Some(email)) => {
+                        batch.push((index, email));
+                        index += 1;
+                    }
+                    Ok(None) => {
+                        exhausted = true;
+                        break;
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            if !self.wait_while_paused().await {
+                self.cancel(batch).await;
+                return;
+            }
+            self.start(batch, permit);
+        }
+    }
+
+    /// Wait for a free request slot, or `None` if the send is cancelled meanwhile.
+    async fn permit(&mut self) -> Option<OwnedSemaphorePermit> {
+        let permits = self.permits.clone();
+        tokio::select! {
+            permit = permits.acquire_owned() => permit.ok(),
+            _ = self.cancelled() => None,
+        }
+    }
+
+    /// The next email of the stream, or `None` at its end or if the send is cancelled.
+    async fn next<S: Stream<Item = Email>>(&mut self, emails: &mut Pin<&mut S>) -> Option<Email> {
+        tokio::select! {
+            email = poll_fn(|cx| emails.as_mut().poll_next(cx)) => email,
+            _ = self.cancelled() => None,
+        }
+    }
+
+    /// Resolve once the send is cancelled or the outcome stream dropped.
+    async fn cancelled(&mut self) {
+        let outcomes = self.outcomes.clone();
+        let state = &mut self.state;
+        let cancelled = async {
+            // Without any handle left, the send can no longer be cancelled.
+            if state
+                .wait_for(|state| *state == BulkState::Cancelled)
+                .await
+                .is_err()
+            {
+                std::future::pending::<()>().await;
+            }
+        };
+        tokio::select! {
+            _ = cancelled => {}
+            _ = outcomes.closed() => {}
+        }
+    }
+
+    /// Wait while paused; returns `false` if the send is cancelled.
+    async fn wait_while_paused(&mut self) -> bool {
+        if self.outcomes.is_closed() {
+            return false;
+        }
+        match self
+            .state
+            .wait_for(|state| *state != BulkState::Paused)
+            .await
+        {
+            Ok(state) => *state == BulkState::Running,
+            Err(_) => true,
+        }
+    }
+
+    /// Report emails read but not sent as cancelled.
+    async fn cancel(&self, batch: Vec<(usize, Email)>) {
+        let err = Arc::new(LanefulError::Cancelled);
+        for (index, email) in batch {
+            let outcome = BulkOutcome {
+                index,
+                recipients: email.to,
+                result: Err(err.clone()),
+            };
+            if self.outcomes.send(outcome).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Send a batch in the background, holding `permit` until its outcomes are reported.
+    fn start(&self, batch: Vec<(usize, Email)>, permit: OwnedSemaphorePermit) {
+        let client = self.client.clone();
+        let options = self.options.clone();
+        let outcomes = self.outcomes.clone();
+        tokio::spawn(async move {
+            let retry = client.may_drop_emails().then(|| batch.clone());
+            let (recipients, result) = send_batch(&client, &options, batch).await;
+            match (result, retry) {
+                // Middleware or the recipient policy dropped emails: sent one at a time,
+                // the dropped ones fail with their own error.
+                (Err(err), Some(batch)) if client::dropped_emails(&err) => {
+                    for email in batch {
+                        let (recipients, result) = send_batch(&client, &options, vec![email]).await;
+                        if !report(&outcomes, recipients, result).await {
+                            break;
+                        }
+                    }
+                }
+                (result, _) => {
+                    report(&outcomes, recipients, result).await;
+                }
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// Send a batch, returning the positions and recipients of its emails with the result.
+///
+/// The send fails if middleware or the recipient policy drop emails, as the results
+/// would no longer line up with the emails.
+async fn send_batch(
+    client: &LanefulClient,
+    options: &SendOptions,
+    batch: Vec<(usize, Email)>,
+) -> (Vec<(usize, Vec<EmailAddress>)>, Result<SendEmailResponse>) {
+    // Batches are cut by timing, so they are told apart by the emails they carry.
+    let first = batch.first().map_or(0, |(index, _)| *index);
+    let last = batch.last().map_or(0, |(index, _)| *index);
+    let options = options
+        .part(format_args!("emails-{first}-{last}"))
+        .keep_positions();
+    let (indices, emails): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+    let recipients = emails.iter().map(|email| email.to.clone());
+    let recipients = indices.into_iter().zip(recipients).collect();
+    let result = client.send_with_options_async(emails, &options).await;
+    (recipients, result)
+}
+
+/// Report the outcomes of a batch; `false` if the outcome stream was dropped.
+async fn report(
+    outcomes: &mpsc::Sender<BulkOutcome>,
+    recipients: Vec<(usize, Vec<EmailAddress>)>,
+    result: Result<SendEmailResponse>,
+) -> bool {
+    let result = result.map_err(Arc::new);
+    for (position, (index, recipients)) in recipients.into_iter().enumerate() {
+        let outcome = BulkOutcome {
+            index,
+            recipients,
+            result: result
+                .as_ref()
+                .map(|response| response.for_email(position))
+                .map_err(Arc::clone),
+        };
+        if outcomes.send(outcome).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,333 @@
+//! Circuit breaker for sustained API outages.
+//!
+//! While the breaker is open, sends fail immediately with
+//! [`LanefulError::CircuitOpen`] instead of waiting for a connection timeout. After a
+//! cool-down a limited number of probe requests are let through (half-open); enough
+//! successful probes close the circuit again, a failed probe re-opens it.
+
+use crate::error::{LanefulError, Result};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(30);
+const DEFAULT_HALF_OPEN_MAX_CALLS: u32 = 1;
+const DEFAULT_SUCCESS_THRESHOLD: u32 = 1;
+
+type StateChangeCallback = Arc<dyn Fn(CircuitState, CircuitState) + Send + Sync>;
+
+/// State of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally; consecutive failures are counted.
+    Closed,
+    /// Requests are rejected until the cool-down elapses.
+    Open,
+    /// A limited number of probe requests are allowed through.
+    HalfOpen,
+}
+
+/// Builder for [`CircuitBreaker`].
+#[derive(Clone)]
+pub struct CircuitBreakerBuilder {
+    failure_threshold: u32,
+    cool_down: Duration,
+    half_open_max_calls: u32,
+    success_threshold: u32,
+    on_state_change: Option<StateChangeCallback>,
+}
+
+impl Default for CircuitBreakerBuilder {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cool_down: DEFAULT_COOL_DOWN,
+            half_open_max_calls: DEFAULT_HALF_OPEN_MAX_CALLS,
+            success_threshold: DEFAULT_SUCCESS_THRESHOLD,
+            on_state_change: None,
+        }
+    }
+}
+
+impl fmt::Debug for CircuitBreakerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreakerBuilder")
+            .field("failure_threshold", &self.failure_threshold)
+            .field("cool_down", &self.cool_down)
+            .field("half_open_max_calls", &self.half_open_max_calls)
+            .field("success_threshold", &self.success_threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CircuitBreakerBuilder {
+    /// Consecutive failures that open the circuit (default: 5).
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// How long the circuit stays open before probing (default: 30s).
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Concurrent probe requests allowed while half-open (default: 1).
+    pub fn half_open_max_calls(mut self, calls: u32) -> Self {
+        self.half_open_max_calls = calls.max(1);
+        self
+    }
+
+    /// Successful probes needed to close the circuit again (default: 1).
+    pub fn success_threshold(mut self, threshold: u32) -> Self {
+        self.success_threshold = threshold.max(1);
+        self
+    }
+
+    /// Call `callback(from, to)` on every state transition, e.g. to raise an alert.
+    ///
+    /// The callback runs on the thread that triggered the transition and must not block.
+    pub fn on_state_change(
+        mut self,
+        callback: impl Fn(CircuitState, CircuitState) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_state_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Build the circuit breaker.
+    pub fn build(self) -> CircuitBreaker {
+        CircuitBreaker {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    state: CircuitState::Closed,
+                    consecutive_failures: 0,
+                    half_open_successes: 0,
+                    half_open_in_flight: 0,
+                    opened_at: None,
+                }),
+                config: self,
+            }),
+        }
+    }
+}
+
+/// Shared circuit breaker, installed with
+/// [`LanefulClient::circuit_breaker`](crate::LanefulClient::circuit_breaker).
+///
+/// Connection errors, timeouts and `5xx` responses count as failures; other API errors
+/// show the service is reachable and count as successes.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::{CircuitBreaker, CircuitState};
+/// use std::time::Duration;
+///
+/// let breaker = CircuitBreaker::builder()
+///     .failure_threshold(3)
+///     .cool_down(Duration::from_secs(10))
+///     .on_state_change(|from, to| eprintln!("laneful circuit {from:?} -> {to:?}"))
+///     .build();
+///
+/// assert_eq!(breaker.state(), CircuitState::Closed);
+/// ```
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    config: CircuitBreakerBuilder,
+}
+
+struct State {
+    state: CircuitState,
+    consecutive_failures: u32,
+    half_open_successes: u32,
+    half_open_in_flight: u32,
+    opened_at: Option<Instant>,
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("state", &self.state())
+            .field("config", &self.inner.config)
+            .finish()
+    }
+}
+
+/// Admission to send one request, obtained from [`CircuitBreaker::acquire`].
+///
+/// Report the outcome with [`success`](Self::success) or [`failure`](Self::failure).
+/// A permit dropped without an outcome (e.g. a cancelled future) frees its probe slot
+/// without affecting the circuit.
+#[must_use = "report the outcome with `success` or `failure`"]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl CircuitPermit<'_> {
+    /// Record a successful request.
+    pub fn success(mut self) {
+        let probe = std::mem::take(&mut self.probe);
+        self.breaker.record(true, probe);
+    }
+
+    /// Record a failed request.
+    pub fn failure(mut self) {
+        let probe = std::mem::take(&mut self.probe);
+        self.breaker.record(false, probe);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            let mut state = self.breaker.lock();
+            state.half_open_in_flight = state.half_open_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker builder.
+    pub fn builder() -> CircuitBreakerBuilder {
+        CircuitBreakerBuilder::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// Ask to send a request, failing with [`LanefulError::CircuitOpen`] while open.
+    pub fn acquire(&self) -> Result<CircuitPermit<'_>> {
+        let mut state = self.lock();
+        let mut transition = None;
+
+        if state.state == CircuitState::Open {
+            let cooled_down = state
+                .opened_at
+                .is_none_or(|opened_at| opened_at.elapsed() >= self.inner.config.cool_down);
+            if !cooled_down {
+                return Err(LanefulError::CircuitOpen);
+            }
+            transition = state.transition(CircuitState::HalfOpen);
+        }
+
+        let probe = state.state == CircuitState::HalfOpen;
+        let admitted = if probe {
+            if state.half_open_in_flight < self.inner.config.half_open_max_calls {
+                state.half_open_in_flight += 1;
+                true
+            } else {
+                false
+            }
+        } else {
+            true
+        };
+        drop(state);
+
+        self.notify(transition);
+        if admitted {
+            Ok(CircuitPermit {
+                breaker: self,
+                probe,
+            })
+        } else {
+            Err(LanefulError::CircuitOpen)
+        }
+    }
+
+    /// Force the circuit back to closed, e.g. after a manual health check.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.consecutive_failures = 0;
+        let transition = state.transition(CircuitState::Closed);
+        drop(state);
+        self.notify(transition);
+    }
+
+    fn record(&self, success: bool, probe: bool) {
+        let config = &self.inner.config;
+        let mut state = self.lock();
+        if probe {
+            state.half_open_in_flight = state.half_open_in_flight.saturating_sub(1);
+        }
+
+        let transition = match (state.state, success) {
+            (CircuitState::Closed, true) => {
+                state.consecutive_failures = 0;
+                None
+            }
+            (CircuitState::Closed, false) => {
+                state.consecutive_failures += 1;
+                if state.consecutive_failures >= config.failure_threshold {
+                    state.transition(CircuitState::Open)
+                } else {
+                    None
+                }
+            }
+            (CircuitState::HalfOpen, true) => {
+                state.half_open_successes += 1;
+                if state.half_open_successes >= config.success_threshold {
+                    state.consecutive_failures = 0;
+                    state.transition(CircuitState::Closed)
+                } else {
+                    None
+                }
+            }
+            (CircuitState::HalfOpen, false) => state.transition(CircuitState::Open),
+            // Late results of requests admitted before the circuit opened.
+            (CircuitState::Open, _) => None,
+        };
+        drop(state);
+
+        self.notify(transition);
+    }
+
+    fn notify(&self, transition: Option<(CircuitState, CircuitState)>) {
+        if let (Some((from, to)), Some(callback)) = (transition, &self.inner.config.on_state_change)
+        {
+            callback(from, to);
+        }
+    }
+}
+
+impl State {
+    fn transition(&mut self, to: CircuitState) -> Option<(CircuitState, CircuitState)> {
+        let from = self.state;
+        if from == to {
+            return None;
+        }
+
+        self.state = to;
+        self.half_open_successes = 0;
+        self.opened_at = (to == CircuitState::Open).then(Instant::now);
+        Some((from, to))
+    }
+}
+
+/// Whether an error indicates the API is unavailable (as opposed to rejecting a request).
+pub(crate) fn is_outage(err: &LanefulError) -> bool {
+    match err {
+        LanefulError::HttpError(err) => {
+            err.is_connect()
+                || err.is_timeout()
+                || err.is_request()
+                || err.status().is_some_and(|s| s.is_server_error())
+        }
+        LanefulError::ApiStatusError { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
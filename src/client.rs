@@ -1,17 +1,321 @@
 //! Laneful API client.
 
+use crate::builder::EmailBuilder;
+use crate::circuit_breaker::{self, CircuitBreaker, CircuitPermit};
+use crate::config::LanefulConfig;
+use crate::defaults::EmailDefaults;
+use crate::dry_run::{DryRunReport, DryRunRequest};
+use crate::endpoint::{
+    DEFAULT_HEALTH_COOL_DOWN, EmailRouter, Endpoint, EndpointEntry, EndpointPool,
+};
 use crate::error::{LanefulError, Result};
-use crate::models::{ApiErrorResponse, Email, SendEmailRequest, SendEmailResponse};
+use crate::idempotency::{self, IDEMPOTENCY_KEY_HEADER, IdempotencyStore};
+use crate::letter_opener::LetterOpener;
+use crate::middleware::Middleware;
+use crate::models::{
+    ApiErrorResponse, Email, EmailResult, SendEmailRequest, SendEmailResponse, SendStatus,
+};
+use crate::partial::{self, BatchOutcome, PartialSend};
+use crate::personalized::{Expanded, ExpandedRequest, Personalized};
+use crate::rate_limit::{Priority, RateLimiter};
+use crate::recipient_policy::RecipientPolicy;
+use crate::retry::RetryPolicy;
+use crate::secret::SecretString;
+use crate::telemetry;
+use crate::trace::{self, Span, Summary};
+use reqwest::StatusCode;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::Arc;
 #[cfg(feature = "async")]
 use std::sync::OnceLock;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(3600);
+
+/// Placeholder credentials of a client that only captures sends; never used in a request.
+const LETTER_OPENER_ENDPOINT: &str = "http://localhost";
+const LETTER_OPENER_API_KEY: &str = "letter-opener";
+/// Error of a send made with [`SendOptions::keep_positions`] whose emails were dropped.
+const DROPPED_EMAILS: &str = "middleware or the recipient policy dropped emails";
+
+/// Whether a send made with [`SendOptions::keep_positions`] failed because middleware or
+/// the recipient policy dropped some of its emails, which sent one at a time would each
+/// get their own outcome.
+pub(crate) fn dropped_emails(err: &LanefulError) -> bool {
+    matches!(err, LanefulError::ValidationError(message) if message == DROPPED_EMAILS)
+}
+
+/// Per-call options for [`LanefulClient::send_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    idempotency_key: Option<String>,
+    priority: Priority,
+    profile: Option<String>,
+    /// Whether the send fails if middleware or the recipient policy drop emails, for
+    /// callers matching results to the emails they passed.
+    keep_positions: bool,
+}
+
+impl SendOptions {
+    /// Create default send options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the idempotency key sent in the `Idempotency-Key` header.
+    ///
+    /// Retrying a send with the same key is recognised as a repeat, both by the API
+    /// and by the client's [`IdempotencyStore`], if one is configured.
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    /// Set the rate-limiter lane of the send (default: [`Priority::Transactional`]).
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Merge the named [profile](LanefulClient::profile) into the emails, on top of
+    /// the client's [defaults](LanefulClient::defaults).
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// These options, failing the send if middleware or the recipient policy drop
+    /// emails.
+    pub(crate) fn keep_positions(self) -> Self {
+        Self {
+            keep_positions: true,
+            ..self
+        }
+    }
+
+    /// These options without an idempotency key.
+    #[cfg(feature = "queue")]
+    pub(crate) fn without_idempotency_key(self) -> Self {
+        Self {
+            idempotency_key: None,
+            ..self
+        }
+    }
+
+    /// The options for one of the requests of a send split by the caller: the
+    /// idempotency key, if any, gets `part` appended so that the request is not taken
+    /// for a repeat of another part.
+    pub(crate) fn part(&self, part: impl fmt::Display) -> Self {
+        Self {
+            idempotency_key: self
+                .idempotency_key
+                .as_ref()
+                .map(|key| format!("{key}:{part}")),
+            ..self.clone()
+        }
+    }
+}
+
+/// Emails of a failed [`EmailQueue`](crate::EmailQueue) batch to send one at a time.
+#[cfg(feature = "queue")]
+pub(crate) enum Resend {
+    /// As prepared for the request the API rejected.
+    Prepared(Vec<Email>),
+    /// As passed in, before middleware or the recipient policy dropped some of them.
+    Original(Vec<Email>),
+}
+
+/// Emails routed to a region, with their positions among those of the send.
+type Route = (Option<String>, Vec<usize>, Vec<Email>);
+
+/// A serialized request ready to be dispatched.
+struct PreparedRequest {
+    body: Vec<u8>,
+    email_count: usize,
+    /// Recipients across `to`, `cc` and `bcc`.
+    recipient_count: usize,
+    idempotency_key: Option<String>,
+    priority: Priority,
+    /// Region the emails are pinned to by the router.
+    region: Option<String>,
+    /// Positions of the emails among those of the send, if it is split across requests.
+    positions: Option<Vec<usize>>,
+    /// Span covering the request, including retries.
+    span: Span,
+}
+
+/// One HTTP attempt against one endpoint.
+struct Attempt<'a> {
+    entry: &'a EndpointEntry,
+    span: Span,
+    started: Instant,
+    /// Status of the response, once received.
+    status: Option<StatusCode>,
+}
+
+impl<'a> Attempt<'a> {
+    fn start(entry: &'a EndpointEntry, number: u32) -> Self {
+        Self {
+            entry,
+            span: Span::attempt(entry, number),
+            started: Instant::now(),
+            status: None,
+        }
+    }
+
+    fn response(&mut self, status: StatusCode, headers: &HeaderMap) {
+        self.status = Some(status);
+        self.span.record_response(status.as_u16(), headers);
+    }
+}
+
+/// The blocking or async half of the request path.
+///
+/// Retries, failover and instrumentation are written once, as async code generic over
+/// the transport. The blocking transport completes every step without suspending, so
+/// its futures are driven to completion by [`block_on`].
+trait Transport {
+    /// Wait before a retry.
+    async fn sleep(&self, delay: Duration);
+
+    /// Wait for the rate limiter to let `emails` emails through.
+    async fn acquire(&self, limiter: &RateLimiter, emails: usize, priority: Priority);
+
+    /// Make one HTTP attempt of a request.
+    async fn post(
+        &self,
+        client: &LanefulClient,
+        headers: HeaderMap,
+        prepared: &PreparedRequest,
+        attempt: &mut Attempt<'_>,
+    ) -> Result<SendEmailResponse>;
+}
+
+/// Transport of the blocking API.
+struct Blocking;
+
+impl Transport for Blocking {
+    async fn sleep(&self, delay: Duration) {
+        std::thread::sleep(delay);
+    }
+
+    async fn acquire(&self, limiter: &RateLimiter, emails: usize, priority: Priority) {
+        limiter.acquire_blocking(emails, priority);
+    }
+
+    async fn post(
+        &self,
+        client: &LanefulClient,
+        headers: HeaderMap,
+        prepared: &PreparedRequest,
+        attempt: &mut Attempt<'_>,
+    ) -> Result<SendEmailResponse> {
+        let request = client
+            .blocking_client()
+            .post(attempt.entry.send_url())
+            .headers(headers)
+            .body(prepared.body.clone());
+        match request.send() {
+            Ok(response) => client.handle_response_sync(response, attempt),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Transport of the async API.
+#[cfg(feature = "async")]
+struct NonBlocking;
+
+#[cfg(feature = "async")]
+impl Transport for NonBlocking {
+    async fn sleep(&self, delay: Duration) {
+        tokio::time::sleep(delay).await;
+    }
+
+    async fn acquire(&self, limiter: &RateLimiter, emails: usize, priority: Priority) {
+        limiter.acquire(emails, priority).await;
+    }
+
+    async fn post(
+        &self,
+        client: &LanefulClient,
+        headers: HeaderMap,
+        prepared: &PreparedRequest,
+        attempt: &mut Attempt<'_>,
+    ) -> Result<SendEmailResponse> {
+        let request = client
+            .async_client
+            .post(attempt.entry.send_url())
+            .headers(headers)
+            .body(prepared.body.clone());
+        match request.send().await {
+            Ok(response) => client.handle_response_async(response, attempt).await,
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Drive a future of the [`Blocking`] transport, which never suspends, to completion.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("the blocking transport never suspends"),
+    }
+}
+
+/// Timeouts applied to the HTTP clients.
+#[derive(Debug, Clone, Copy, Default)]
+struct HttpSettings {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+}
+
+impl HttpSettings {
+    fn blocking_client(self) -> reqwest::blocking::Client {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder.build().expect("failed to build HTTP client")
+    }
+
+    #[cfg(feature = "async")]
+    fn async_client(self) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder.build().expect("failed to build HTTP client")
+    }
+}
 
 /// Client for the Laneful Email API.
 #[derive(Debug, Clone)]
 pub struct LanefulClient {
-    /// Base URL for API calls.
-    base_url: String,
-    /// API key for authentication.
-    api_key: String,
+    /// Endpoints in failover order, with their API keys and health.
+    endpoints: Arc<EndpointPool>,
+    /// How long a failing endpoint is skipped.
+    health_cool_down: Duration,
+    /// Hook pinning emails to a region.
+    router: Option<EmailRouter>,
+    /// Timeouts of the HTTP clients.
+    http_settings: HttpSettings,
     /// Blocking HTTP client (always available).
     #[cfg(feature = "async")]
     blocking_client: OnceLock<reqwest::blocking::Client>,
@@ -20,6 +324,32 @@ pub struct LanefulClient {
     /// Async HTTP client (available when async feature is enabled).
     #[cfg(feature = "async")]
     async_client: reqwest::Client,
+    /// Cache of completed sends, keyed by idempotency key.
+    idempotency_store: Option<Arc<dyn IdempotencyStore>>,
+    /// How long completed sends are remembered in the store.
+    idempotency_ttl: Duration,
+    /// Whether to derive an idempotency key from the payload when none is given.
+    auto_idempotency_keys: bool,
+    /// Shared client-side rate limiter.
+    rate_limiter: Option<RateLimiter>,
+    /// Circuit breaker guarding against sustained outages.
+    circuit_breaker: Option<CircuitBreaker>,
+    /// Retry policy for transient failures.
+    retry_policy: RetryPolicy,
+    /// Defaults applied to every email.
+    defaults: Option<EmailDefaults>,
+    /// Named defaults selected per send.
+    profiles: Arc<HashMap<String, EmailDefaults>>,
+    /// Whether traces include recipient addresses and subjects in clear text.
+    trace_pii: bool,
+    /// Hooks run around every send, in order.
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// Redirect or allowlist applied to the recipients of every send.
+    recipient_policy: Option<RecipientPolicy>,
+    /// Whether sends are validated and serialized without calling the API.
+    dry_run: bool,
+    /// Development transport capturing sends to files instead of calling the API.
+    letter_opener: Option<LetterOpener>,
 }
 
 impl LanefulClient {
@@ -39,7 +369,7 @@ impl LanefulClient {
     ///
     /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
     /// ```
-    pub fn new(endpoint: impl Into<String>, api_key: impl Into<String>) -> Result<Self> {
+    pub fn new(endpoint: impl Into<String>, api_key: impl Into<SecretString>) -> Result<Self> {
         let endpoint = endpoint.into();
 
         if endpoint.is_empty() {
@@ -75,44 +405,454 @@ impl LanefulClient {
     ///     "my-api-key"
     /// ).unwrap();
     /// ```
-    pub fn with_base_url(base_url: impl Into<String>, api_key: impl Into<String>) -> Result<Self> {
-        let base_url = base_url.into().trim_end_matches('/').to_string();
-        let api_key = api_key.into();
-
-        if base_url.is_empty() {
-            return Err(LanefulError::ConfigError("base_url cannot be empty".into()));
-        }
+    pub fn with_base_url(
+        base_url: impl Into<String>,
+        api_key: impl Into<SecretString>,
+    ) -> Result<Self> {
+        Self::with_endpoints(vec![Endpoint::new(base_url)], api_key)
+    }
 
-        if api_key.is_empty() {
-            return Err(LanefulError::ConfigError("api_key cannot be empty".into()));
-        }
+    /// Create a client that fails over between several endpoints.
+    ///
+    /// Endpoints are tried in order. An endpoint that fails with a connection error,
+    /// timeout or `5xx` response is skipped for the [health cool-down](Self::health_cool_down)
+    /// and the request is retried on the next one. `api_key` is used for endpoints without
+    /// their own key.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{Endpoint, LanefulClient};
+    ///
+    /// let client = LanefulClient::with_endpoints(
+    ///     vec![
+    ///         Endpoint::new("https://us.api.example.com").region("us"),
+    ///         Endpoint::new("https://eu.api.example.com").region("eu").api_key("eu-key"),
+    ///         Endpoint::new("https://eu-backup.api.example.com").region("eu").api_key("eu-key"),
+    ///     ],
+    ///     "us-key",
+    /// )
+    /// .unwrap()
+    /// .router(|email| {
+    ///     email
+    ///         .to
+    ///         .iter()
+    ///         .any(|to| to.email.ends_with(".de"))
+    ///         .then(|| "eu".to_string())
+    /// });
+    /// ```
+    pub fn with_endpoints(
+        endpoints: Vec<Endpoint>,
+        api_key: impl Into<SecretString>,
+    ) -> Result<Self> {
+        let endpoints = Arc::new(EndpointPool::new(endpoints, &api_key.into())?);
+        let http_settings = HttpSettings::default();
 
         #[cfg(feature = "async")]
         let blocking_client = OnceLock::new();
         #[cfg(not(feature = "async"))]
-        let blocking_client = reqwest::blocking::Client::new();
+        let blocking_client = http_settings.blocking_client();
 
         #[cfg(feature = "async")]
-        let async_client = reqwest::Client::new();
+        let async_client = http_settings.async_client();
 
         Ok(Self {
-            base_url,
-            api_key,
+            endpoints,
+            health_cool_down: DEFAULT_HEALTH_COOL_DOWN,
+            router: None,
+            http_settings,
             blocking_client,
             #[cfg(feature = "async")]
             async_client,
+            idempotency_store: None,
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            auto_idempotency_keys: false,
+            rate_limiter: None,
+            circuit_breaker: None,
+            retry_policy: RetryPolicy::default(),
+            defaults: None,
+            profiles: Arc::default(),
+            trace_pii: false,
+            middleware: Vec::new(),
+            recipient_policy: None,
+            dry_run: false,
+            letter_opener: None,
         })
     }
 
-    /// Get the API URL for the send endpoint.
-    fn api_url(&self) -> String {
-        format!("{}/v1/email/send", self.base_url)
+    /// Create a client that captures every send with a [`LetterOpener`] writing to `dir`.
+    ///
+    /// No credentials are needed: the client never calls the API.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::LanefulClient;
+    ///
+    /// let client = LanefulClient::with_letter_opener("tmp/emails").unwrap();
+    /// ```
+    pub fn with_letter_opener(dir: impl Into<PathBuf>) -> Result<Self> {
+        Ok(
+            Self::with_base_url(LETTER_OPENER_ENDPOINT, LETTER_OPENER_API_KEY)?
+                .letter_opener(LetterOpener::new(dir)),
+        )
+    }
+
+    /// Create a client from a [`LanefulConfig`].
+    ///
+    /// When `letter_opener_dir` is set, sends are captured to files and the endpoint and
+    /// API key may be omitted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{LanefulClient, LanefulConfig};
+    ///
+    /// let config = LanefulConfig::from_file("laneful.json").unwrap();
+    /// let client = LanefulClient::from_config(&config).unwrap();
+    /// ```
+    pub fn from_config(config: &LanefulConfig) -> Result<Self> {
+        let mut client = match &config.letter_opener_dir {
+            Some(dir) if config.endpoint.is_none() => Self::with_letter_opener(dir.clone())?,
+            Some(dir) => Self::new(config.endpoint()?, config.api_key()?)?
+                .letter_opener(LetterOpener::new(dir.clone())),
+            None => Self::new(config.endpoint()?, config.api_key()?)?,
+        }
+        .retry_policy(config.retry);
+        if let Some(timeout) = config.timeout {
+            client = client.timeout(timeout);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if !config.defaults.is_empty() {
+            client = client.defaults(config.defaults.clone());
+        }
+        client = client.trace_pii(config.trace_pii).dry_run(config.dry_run);
+        if let Some(policy) = config.recipient_policy() {
+            client = client.recipient_policy(policy);
+        }
+        for (name, profile) in &config.profiles {
+            client = client.profile(name.clone(), profile.clone());
+        }
+        Ok(client)
+    }
+
+    /// Create a client from the environment.
+    ///
+    /// Reads the configuration file named by `LANEFUL_CONFIG`, if set, and the
+    /// `LANEFUL_*` environment variables; see [`LanefulConfig::load`]. At minimum,
+    /// `LANEFUL_ENDPOINT` and `LANEFUL_API_KEY` must be set, unless `LANEFUL_LETTER_OPENER_DIR` is.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::LanefulClient;
+    ///
+    /// let client = LanefulClient::from_env().unwrap();
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        Self::from_config(&LanefulConfig::load()?)
+    }
+
+    /// Set the timeout for a whole request, from connecting until the response body
+    /// has been read (default: none).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_settings.timeout = Some(timeout);
+        self.rebuild_http_clients();
+        self
+    }
+
+    /// Set the timeout for establishing a connection (default: none).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http_settings.connect_timeout = Some(timeout);
+        self.rebuild_http_clients();
+        self
+    }
+
+    fn rebuild_http_clients(&mut self) {
+        #[cfg(feature = "async")]
+        {
+            self.blocking_client = OnceLock::new();
+            self.async_client = self.http_settings.async_client();
+        }
+        #[cfg(not(feature = "async"))]
+        {
+            self.blocking_client = self.http_settings.blocking_client();
+        }
+    }
+
+    /// Include recipient addresses and subjects in clear text in `tracing` spans
+    /// (default: `false`, they are recorded as truncated SHA-256 hashes).
+    ///
+    /// Only enable this in development. Has no effect without the `tracing` feature.
+    pub fn trace_pii(mut self, include: bool) -> Self {
+        self.trace_pii = include;
+        self
+    }
+
+    /// Add a [`Middleware`] hook, run after the ones added before it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{LanefulClient, LanefulError, Middleware, SendEmailResponse};
+    ///
+    /// #[derive(Debug)]
+    /// struct Audit;
+    ///
+    /// impl Middleware for Audit {
+    ///     fn after_response(&self, result: Result<&SendEmailResponse, &LanefulError>) {
+    ///         if let Err(err) = result {
+    ///             eprintln!("send failed: {err}");
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .middleware(Audit);
+    /// ```
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Rewrite or drop recipients of every send with a [`RecipientPolicy`], e.g. to keep a
+    /// staging environment from emailing real customers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{LanefulClient, RecipientPolicy};
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .recipient_policy(RecipientPolicy::redirect_to("qa@example.com").allow_domain("example.com"));
+    /// ```
+    pub fn recipient_policy(mut self, policy: RecipientPolicy) -> Self {
+        self.recipient_policy = Some(policy);
+        self
+    }
+
+    /// Validate and serialize every send without calling the API (default: `false`).
+    ///
+    /// Sends return a response with status `dry_run`; use
+    /// [`send_dry_run`](Self::send_dry_run) to get the serialized requests.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Capture sends with a [`LetterOpener`] instead of calling the API (default: none).
+    ///
+    /// Emails are prepared and validated exactly as for a real send, then written to
+    /// files, one directory per email. Sends return a response with status `captured`.
+    pub fn letter_opener(mut self, opener: LetterOpener) -> Self {
+        self.letter_opener = Some(opener);
+        self
+    }
+
+    /// Retry failed sends according to a [`RetryPolicy`] (default: no retries).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{LanefulClient, RetryPolicy};
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .retry_policy(RetryPolicy::new(3))
+    ///     .auto_idempotency_keys(true);
+    /// ```
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Merge [`EmailDefaults`] into every email sent by this client.
+    ///
+    /// See [`EmailDefaults`] for the merge rules.
+    pub fn defaults(mut self, defaults: EmailDefaults) -> Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// Register named defaults, selected per send with [`SendOptions::profile`].
+    ///
+    /// A profile is layered over the client's [defaults](Self::defaults): values set in
+    /// the profile win, and the email's own values win over both.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{EmailDefaults, LanefulClient, SendOptions, Tracking};
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .defaults(EmailDefaults::new().from("noreply@example.com", Some("Example")))
+    ///     .profile("transactional", EmailDefaults::new().tag("transactional"))
+    ///     .profile(
+    ///         "marketing",
+    ///         EmailDefaults::new()
+    ///             .from("news@example.com", Some("Example News"))
+    ///             .tag("marketing")
+    ///             .tracking(Tracking {
+    ///                 opens: Some(true),
+    ///                 clicks: Some(true),
+    ///                 ..Tracking::default()
+    ///             }),
+    ///     );
+    ///
+    /// let email = client
+    ///     .profile_email("marketing")
+    ///     .unwrap()
+    ///     .to("user@example.com", None)
+    ///     .subject("This week at Example")
+    ///     .text_content("News!")
+    ///     .build()
+    ///     .unwrap();
+    /// client
+    ///     .send_with_options(vec![email], &SendOptions::new().profile("marketing"))
+    ///     .unwrap();
+    /// ```
+    pub fn profile(mut self, name: impl Into<String>, defaults: EmailDefaults) -> Self {
+        Arc::make_mut(&mut self.profiles).insert(name.into(), defaults);
+        self
+    }
+
+    /// Create an email builder pre-filled with the client's [defaults](Self::defaults).
+    pub fn email(&self) -> EmailBuilder {
+        self.defaults
+            .as_ref()
+            .map(EmailDefaults::builder)
+            .unwrap_or_default()
+    }
+
+    /// Create an email builder pre-filled with a [profile](Self::profile) layered over
+    /// the client's defaults.
+    pub fn profile_email(&self, name: &str) -> Result<EmailBuilder> {
+        let profile = self.find_profile(name)?;
+        Ok(match &self.defaults {
+            Some(defaults) => profile.layered_over(defaults).builder(),
+            None => profile.builder(),
+        })
+    }
+
+    fn find_profile(&self, name: &str) -> Result<&EmailDefaults> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| LanefulError::ConfigError(format!("unknown email profile `{name}`")))
+    }
+
+    /// Deduplicate sends through an [`IdempotencyStore`].
+    ///
+    /// Sends carrying an idempotency key that completed within the
+    /// [TTL](Self::idempotency_ttl) return the original response without calling the API.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{InMemoryIdempotencyStore, LanefulClient};
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .idempotency_store(InMemoryIdempotencyStore::new())
+    ///     .auto_idempotency_keys(true);
+    /// ```
+    pub fn idempotency_store(mut self, store: impl IdempotencyStore + 'static) -> Self {
+        self.idempotency_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set how long completed sends are remembered (default: 1 hour).
+    pub fn idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency_ttl = ttl;
+        self
+    }
+
+    /// Derive an idempotency key from each request payload when none is given.
+    ///
+    /// The key is a SHA-256 digest of the serialized [`SendEmailRequest`], so resending
+    /// an identical request is treated as a retry of the original one.
+    pub fn auto_idempotency_keys(mut self, enabled: bool) -> Self {
+        self.auto_idempotency_keys = enabled;
+        self
+    }
+
+    /// Throttle sends through a shared [`RateLimiter`].
+    ///
+    /// The limiter also adapts to `429` responses and rate-limit headers returned by the
+    /// API. Clone the limiter into several clients to share one budget between them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{LanefulClient, RateLimiter};
+    ///
+    /// let limiter = RateLimiter::builder().requests_per_second(10.0).build();
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .rate_limiter(limiter);
+    /// ```
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Fail fast with [`LanefulError::CircuitOpen`] while the API is unavailable.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use laneful_rs::{CircuitBreaker, LanefulClient};
+    /// use std::time::Duration;
+    ///
+    /// let breaker = CircuitBreaker::builder()
+    ///     .failure_threshold(5)
+    ///     .cool_down(Duration::from_secs(30))
+    ///     .build();
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+    ///     .unwrap()
+    ///     .circuit_breaker(breaker);
+    /// ```
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Pin emails to a region: `router` returns the region of an email, or `None` to let
+    /// it use any endpoint.
+    ///
+    /// A request mixing regions is split into one request per region. Pinned emails only
+    /// fail over between endpoints of their region, never outside it.
+    pub fn router(
+        mut self,
+        router: impl Fn(&Email) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.router = Some(EmailRouter(Arc::new(router)));
+        self
+    }
+
+    /// Set how long a failing endpoint is skipped (default: 30s).
+    pub fn health_cool_down(mut self, cool_down: Duration) -> Self {
+        self.health_cool_down = cool_down;
+        self
+    }
+
+    /// Configured endpoints and whether each is currently considered healthy.
+    pub fn endpoint_health(&self) -> Vec<(&Endpoint, bool)> {
+        self.endpoints
+            .entries()
+            .iter()
+            .map(|entry| (&entry.endpoint, entry.is_healthy()))
+            .collect()
     }
 
     #[cfg(feature = "async")]
     fn blocking_client(&self) -> &reqwest::blocking::Client {
         self.blocking_client
-            .get_or_init(reqwest::blocking::Client::new)
+            .get_or_init(|| self.http_settings.blocking_client())
     }
 
     #[cfg(not(feature = "async"))]
@@ -120,6 +860,533 @@ impl LanefulClient {
         &self.blocking_client
     }
 
+    /// Prepare the requests of a send.
+    fn prepare(&self, emails: Vec<Email>, options: &SendOptions) -> Result<Vec<PreparedRequest>> {
+        let emails = self.prepare_emails(emails, options)?;
+        self.prepare_requests(emails, options)
+    }
+
+    /// Merge defaults into the emails and run middleware and the recipient policy.
+    fn prepare_emails(&self, mut emails: Vec<Email>, options: &SendOptions) -> Result<Vec<Email>> {
+        let count = emails.len();
+        // Defaults only fill unset values, so applying the profile first gives it
+        // precedence over the client defaults.
+        let profile = options
+            .profile
+            .as_deref()
+            .map(|name| self.find_profile(name))
+            .transpose()?;
+        for defaults in profile.into_iter().chain(&self.defaults) {
+            emails.iter_mut().for_each(|email| defaults.apply(email));
+        }
+        let mut emails = self.before_send(emails)?;
+        if let Some(policy) = &self.recipient_policy {
+            let had_emails = !emails.is_empty();
+            let report = policy.apply(&mut emails);
+            trace::recipients_rewritten(&report);
+            if had_emails && emails.is_empty() {
+                return Err(LanefulError::ValidationError(
+                    "no recipients left to send to after applying the recipient policy".into(),
+                ));
+            }
+        }
+        if options.keep_positions && emails.len() != count {
+            return Err(LanefulError::ValidationError(DROPPED_EMAILS.into()));
+        }
+        Ok(emails)
+    }
+
+    /// Split the emails by region, serialize each request and resolve its idempotency key.
+    fn prepare_requests(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<Vec<PreparedRequest>> {
+        let groups = self.route(emails);
+        let split = groups.len() > 1;
+
+        groups
+            .into_iter()
+            .map(|(region, positions, emails)| {
+                let email_count = emails.len();
+                let recipient_count = emails
+                    .iter()
+                    .map(|email| {
+                        email.to.len()
+                            + email.cc.as_ref().map_or(0, Vec::len)
+                            + email.bcc.as_ref().map_or(0, Vec::len)
+                    })
+                    .sum();
+                let summary = Summary::new(&emails, self.trace_pii);
+                let request = SendEmailRequest { emails };
+                let body = serde_json::to_vec(&request)?;
+                let idempotency_key =
+                    self.idempotency_key(&body, region.as_deref(), split, options);
+
+                let span = Span::send(
+                    &summary,
+                    email_count,
+                    region.as_deref(),
+                    idempotency_key.as_deref(),
+                );
+
+                Ok(PreparedRequest {
+                    body,
+                    email_count,
+                    recipient_count,
+                    idempotency_key,
+                    priority: options.priority,
+                    region,
+                    positions: split.then_some(positions),
+                    span,
+                })
+            })
+            .collect()
+    }
+
+    /// Merge defaults into the base of personalized emails and serialize the request.
+    fn prepare_personalized(
+        &self,
+        emails: &Personalized,
+        options: &SendOptions,
+    ) -> Result<PreparedRequest> {
+        let profile = options
+            .profile
+            .as_deref()
+            .map(|name| self.find_profile(name))
+            .transpose()?;
+        // Copy the base only if there are defaults to merge into it.
+        let base = if profile.is_none() && self.defaults.is_none() {
+            Cow::Borrowed(emails.base().as_ref())
+        } else {
+            let mut base = Email::clone(emails.base());
+            for defaults in profile.into_iter().chain(&self.defaults) {
+                defaults.apply(&mut base);
+            }
+            Cow::Owned(base)
+        };
+
+        let expanded = Expanded {
+            base: &base,
+            recipients: emails.personalizations(),
+        };
+        expanded.validate()?;
+        let body = serde_json::to_vec(&ExpandedRequest { emails: expanded })?;
+        let idempotency_key = self.idempotency_key(&body, None, false, options);
+
+        let summary = Summary::personalized(expanded, self.trace_pii);
+        let span = Span::send(&summary, emails.len(), None, idempotency_key.as_deref());
+
+        Ok(PreparedRequest {
+            body,
+            email_count: emails.len(),
+            recipient_count: expanded.recipient_count(),
+            idempotency_key,
+            priority: options.priority,
+            region: None,
+            positions: None,
+            span,
+        })
+    }
+
+    /// Resolve the idempotency key of a serialized request.
+    fn idempotency_key(
+        &self,
+        body: &[u8],
+        region: Option<&str>,
+        split: bool,
+        options: &SendOptions,
+    ) -> Option<String> {
+        // Each part of a split request needs its own key, or the API would treat
+        // the second part as a repeat of the first.
+        match (&options.idempotency_key, region) {
+            (Some(key), Some(region)) if split => Some(format!("{key}:{region}")),
+            (Some(key), _) => Some(key.clone()),
+            (None, _) => self
+                .auto_idempotency_keys
+                .then(|| idempotency::derive_key(body)),
+        }
+    }
+
+    /// Whether middleware or the recipient policy may drop emails from a send.
+    pub(crate) fn may_drop_emails(&self) -> bool {
+        !self.middleware.is_empty() || self.recipient_policy.is_some()
+    }
+
+    /// Whether personalized emails must be expanded to go through the parts of the
+    /// pipeline that work on individual emails.
+    fn expands_personalized(&self) -> bool {
+        !self.middleware.is_empty()
+            || self.recipient_policy.is_some()
+            || self.router.is_some()
+            || self.dry_run
+            || self.letter_opener.is_some()
+    }
+
+    /// Run the middleware's `before_send` hooks over the request.
+    fn before_send(&self, emails: Vec<Email>) -> Result<Vec<Email>> {
+        if self.middleware.is_empty() {
+            return Ok(emails);
+        }
+
+        let had_emails = !emails.is_empty();
+        let mut request = SendEmailRequest { emails };
+        for middleware in &self.middleware {
+            middleware.before_send(&mut request)?;
+        }
+        if had_emails && request.emails.is_empty() {
+            return Err(LanefulError::ValidationError(
+                "no emails left to send after middleware".into(),
+            ));
+        }
+        Ok(request.emails)
+    }
+
+    /// Run the middleware's `after_response` hooks, innermost first.
+    fn after_response(&self, result: &Result<SendEmailResponse>) {
+        for middleware in self.middleware.iter().rev() {
+            middleware.after_response(result.as_ref());
+        }
+    }
+
+    /// Group emails by the region chosen by the router, keeping their order.
+    fn route(&self, emails: Vec<Email>) -> Vec<Route> {
+        let Some(router) = &self.router else {
+            return vec![(None, (0..emails.len()).collect(), emails)];
+        };
+
+        let mut groups: Vec<Route> = Vec::new();
+        for (position, email) in emails.into_iter().enumerate() {
+            let region = (router.0)(&email);
+            match groups.iter_mut().find(|(r, _, _)| *r == region) {
+                Some((_, positions, group)) => {
+                    positions.push(position);
+                    group.push(email);
+                }
+                None => groups.push((region, vec![position], vec![email])),
+            }
+        }
+
+        if groups.is_empty() {
+            groups.push((None, Vec::new(), Vec::new()));
+        }
+        groups
+    }
+
+    /// Combine the responses of the parts of a split request, mapping the per-email
+    /// results of each part back to the positions of its emails.
+    fn merge_responses(
+        responses: Vec<(SendEmailResponse, Option<Vec<usize>>)>,
+    ) -> SendEmailResponse {
+        let mut parts = responses.into_iter();
+        let Some((mut merged, positions)) = parts.next() else {
+            return SendEmailResponse::new(SendStatus::Accepted);
+        };
+        let mut results = Self::reindex(std::mem::take(&mut merged.results), positions.as_deref());
+        for (response, positions) in parts {
+            if response.status != merged.status {
+                merged.status = match (&merged.status, &response.status) {
+                    (SendStatus::Accepted | SendStatus::PartiallyAccepted, _)
+                    | (_, SendStatus::Accepted | SendStatus::PartiallyAccepted) => {
+                        SendStatus::PartiallyAccepted
+                    }
+                    (status, _) => status.clone(),
+                };
+            }
+            results.extend(Self::reindex(response.results, positions.as_deref()));
+            for (key, value) in response.extra {
+                merged.extra.entry(key).or_insert(value);
+            }
+        }
+        results.sort_by_key(|result| result.index);
+        merged.results = results;
+        merged
+    }
+
+    /// Map the indices of a part's results to the positions of its emails in the send.
+    fn reindex(mut results: Vec<EmailResult>, positions: Option<&[usize]>) -> Vec<EmailResult> {
+        if let Some(positions) = positions {
+            for result in &mut results {
+                if let Some(&position) = positions.get(result.index) {
+                    result.index = position;
+                }
+            }
+        }
+        results
+    }
+
+    /// Look up a previous response for the request's idempotency key.
+    fn cached_response(&self, prepared: &PreparedRequest) -> Option<SendEmailResponse> {
+        let store = self.idempotency_store.as_ref()?;
+        store.get(prepared.idempotency_key.as_deref()?)
+    }
+
+    /// Ask the circuit breaker, if any, for permission to send.
+    fn circuit_permit(&self) -> Result<Option<CircuitPermit<'_>>> {
+        self.circuit_breaker
+            .as_ref()
+            .map(CircuitBreaker::acquire)
+            .transpose()
+    }
+
+    /// Record the outcome of a dispatched request.
+    fn finish(
+        &self,
+        prepared: &PreparedRequest,
+        permit: Option<CircuitPermit<'_>>,
+        result: &Result<SendEmailResponse>,
+    ) {
+        if let Some(permit) = permit {
+            match result {
+                Err(err) if circuit_breaker::is_outage(err) => permit.failure(),
+                _ => permit.success(),
+            }
+        }
+
+        if let (Some(store), Some(key), Ok(response)) =
+            (&self.idempotency_store, &prepared.idempotency_key, result)
+        {
+            store.put(key, response, self.idempotency_ttl);
+        }
+    }
+
+    /// Headers of a request to `entry`: credentials, idempotency key and trace context.
+    fn request_headers(
+        &self,
+        entry: &EndpointEntry,
+        prepared: &PreparedRequest,
+    ) -> Result<HeaderMap> {
+        let mut headers = telemetry::propagation_headers();
+        headers.insert(AUTHORIZATION, entry.authorization()?);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if let Some(key) = &prepared.idempotency_key {
+            let key = HeaderValue::from_str(key).map_err(|_| {
+                LanefulError::ValidationError(
+                    "idempotency key contains characters that are not allowed in an HTTP header"
+                        .into(),
+                )
+            })?;
+            headers.insert(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        Ok(headers)
+    }
+
+    /// Send one prepared request over `transport`, retrying according to the retry
+    /// policy.
+    async fn dispatch_with_retry<T: Transport>(
+        &self,
+        transport: &T,
+        prepared: &PreparedRequest,
+    ) -> Result<SendEmailResponse> {
+        let dispatch = async {
+            if let Some(response) = self.cached_response(prepared) {
+                trace::cached();
+                telemetry::cached();
+                return Ok(response);
+            }
+            let mut retry = 0;
+            loop {
+                let result = self.dispatch(transport, prepared, retry + 1).await;
+                if let Err(err) = &result
+                    && let Some(delay) = self.retry_delay(retry, err)
+                {
+                    transport.sleep(delay).await;
+                    retry += 1;
+                    continue;
+                }
+                self.complete(prepared, retry + 1, &result);
+                return result;
+            }
+        };
+        prepared.span.instrument(dispatch).await
+    }
+
+    /// Send one prepared request over `transport`, failing over between endpoints.
+    async fn dispatch<T: Transport>(
+        &self,
+        transport: &T,
+        prepared: &PreparedRequest,
+        attempt: u32,
+    ) -> Result<SendEmailResponse> {
+        let permit = self.circuit_permit()?;
+
+        if let Some(limiter) = &self.rate_limiter {
+            transport
+                .acquire(limiter, prepared.email_count, prepared.priority)
+                .await;
+        }
+
+        let mut result = Err(LanefulError::ConfigError("no endpoint available".into()));
+        for entry in self.endpoints.candidates(prepared.region.as_deref())? {
+            let headers = self.request_headers(entry, prepared)?;
+            let mut attempt = Attempt::start(entry, attempt);
+            let span = attempt.span.clone();
+            result = span
+                .instrument(transport.post(self, headers, prepared, &mut attempt))
+                .await;
+            if self.finish_attempt(attempt, &result) {
+                break;
+            }
+        }
+
+        self.finish(prepared, permit, &result);
+        result
+    }
+
+    /// Whether to retry after a failed attempt, and how long to wait first.
+    fn retry_delay(&self, retry: u32, err: &LanefulError) -> Option<Duration> {
+        if retry >= self.retry_policy.max_retries || !err.is_retryable() {
+            return None;
+        }
+        let delay = self.retry_policy.delay(retry);
+        trace::retry(retry + 1, delay, err);
+        telemetry::retried();
+        Some(delay)
+    }
+
+    /// Record the final outcome of a prepared request after `attempts` attempts.
+    fn complete(
+        &self,
+        prepared: &PreparedRequest,
+        attempts: u32,
+        result: &Result<SendEmailResponse>,
+    ) {
+        match result {
+            Ok(_) => telemetry::sent(prepared.email_count),
+            Err(err) => {
+                trace::failed(attempts, err);
+                telemetry::failed(err);
+            }
+        }
+    }
+
+    /// Record the outcome of an HTTP attempt; returns `false` to fail over to the
+    /// next endpoint.
+    fn finish_attempt(&self, attempt: Attempt<'_>, result: &Result<SendEmailResponse>) -> bool {
+        let latency = attempt.started.elapsed();
+        attempt.span.record_latency(latency);
+        telemetry::attempt(
+            attempt.entry.endpoint.base_url(),
+            attempt.status.map(|status| status.as_u16()),
+            latency,
+        );
+        self.observe_endpoint(attempt.entry, result)
+    }
+
+    /// Track endpoint health from the outcome of a request.
+    fn observe_endpoint(&self, entry: &EndpointEntry, result: &Result<SendEmailResponse>) -> bool {
+        match result {
+            Err(err) if circuit_breaker::is_outage(err) => {
+                trace::endpoint_failed(entry, err);
+                entry.mark_unhealthy(self.health_cool_down);
+                false
+            }
+            _ => {
+                entry.mark_healthy();
+                true
+            }
+        }
+    }
+
+    /// Feed the response status and headers back into the rate limiter.
+    fn observe_response(&self, status: StatusCode, headers: &HeaderMap) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.observe(status, headers);
+        }
+    }
+
+    /// Build the error for a non-success response.
+    fn api_error(status: StatusCode, error_response: Option<ApiErrorResponse>) -> LanefulError {
+        LanefulError::ApiStatusError {
+            status: status.as_u16(),
+            message: error_response
+                .map(|response| response.error)
+                .unwrap_or_else(|| format!("HTTP error: {}", status)),
+        }
+    }
+
+    // ==================== Dry run ====================
+
+    /// Validate and serialize emails exactly as [`send`](Self::send) would, without
+    /// calling the API.
+    ///
+    /// Defaults, middleware, the recipient policy and routing are applied, every email is
+    /// checked with [`Email::validate`] and every region must have an endpoint.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use laneful_rs::{Email, LanefulClient};
+    ///
+    /// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+    /// let email = Email::builder()
+    ///     .from("sender@example.com", None)
+    ///     .to("recipient@example.com", None)
+    ///     .subject("Hello")
+    ///     .text_content("Hello, world!")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let report = client.send_dry_run(vec![email]).unwrap();
+    /// assert_eq!(report.recipient_count(), 1);
+    /// println!("{}", report.requests[0].body);
+    /// ```
+    pub fn send_dry_run(&self, emails: Vec<Email>) -> Result<DryRunReport> {
+        self.send_dry_run_with_options(emails, &SendOptions::default())
+    }
+
+    /// Dry-run a send with per-call [`SendOptions`]; see [`send_dry_run`](Self::send_dry_run).
+    pub fn send_dry_run_with_options(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<DryRunReport> {
+        let emails = self.prepare_emails(emails, options)?;
+        emails.iter().try_for_each(Email::validate)?;
+
+        let requests = self
+            .prepare_requests(emails, options)?
+            .into_iter()
+            .map(|prepared| {
+                // Surface a region without endpoints now rather than in the send window.
+                self.endpoints.candidates(prepared.region.as_deref())?;
+                Ok(DryRunRequest {
+                    body: String::from_utf8(prepared.body).expect("serde_json produces UTF-8"),
+                    email_count: prepared.email_count,
+                    recipient_count: prepared.recipient_count,
+                    region: prepared.region,
+                    idempotency_key: prepared.idempotency_key,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(DryRunReport { requests })
+    }
+
+    /// Response of a send made in [dry-run mode](Self::dry_run).
+    fn dry_run_response(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        self.send_dry_run_with_options(emails, options)?;
+        Ok(SendEmailResponse::new(SendStatus::DryRun))
+    }
+
+    /// Response of a send captured by a [letter opener](Self::letter_opener).
+    fn capture(
+        &self,
+        opener: &LetterOpener,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        let emails = self.prepare_emails(emails, options)?;
+        emails.iter().try_for_each(Email::validate)?;
+        for email in &emails {
+            opener.deliver(email)?;
+        }
+        Ok(SendEmailResponse::new(SendStatus::Captured))
+    }
+
     // ==================== Sync API (always available) ====================
 
     /// Send multiple emails synchronously.
@@ -141,17 +1408,59 @@ impl LanefulClient {
     /// let response = client.send(vec![email]).unwrap();
     /// ```
     pub fn send(&self, emails: Vec<Email>) -> Result<SendEmailResponse> {
-        let request = SendEmailRequest { emails };
+        self.send_with_options(emails, &SendOptions::default())
+    }
 
-        let response = self
-            .blocking_client()
-            .post(self.api_url())
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()?;
+    /// Send multiple emails synchronously with an explicit idempotency key.
+    ///
+    /// Use a key tied to the business event (e.g. the password-reset token id) so that
+    /// retrying after a timeout cannot deliver the email twice.
+    pub fn send_with_idempotency_key(
+        &self,
+        emails: Vec<Email>,
+        key: impl Into<String>,
+    ) -> Result<SendEmailResponse> {
+        self.send_with_options(emails, &SendOptions::new().idempotency_key(key))
+    }
 
-        self.handle_response_sync(response)
+    /// Send multiple emails synchronously with per-call [`SendOptions`].
+    ///
+    /// When a [router](Self::router) splits the emails across regions, one request is
+    /// made per region; an error in a later region does not undo earlier ones.
+    pub fn send_with_options(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        let result = self.send_prepared_sync(emails, options);
+        self.after_response(&result);
+        result
+    }
+
+    /// Prepare and dispatch the emails; the part of a send wrapped by middleware.
+    fn send_prepared_sync(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        if self.dry_run {
+            return self.dry_run_response(emails, options);
+        }
+        if let Some(opener) = &self.letter_opener {
+            return self.capture(opener, emails, options);
+        }
+
+        let mut responses = Vec::new();
+        for prepared in self.prepare(emails, options)? {
+            let response = self.dispatch_with_retry_sync(&prepared)?;
+            responses.push((response, prepared.positions));
+        }
+        Ok(Self::merge_responses(responses))
+    }
+
+    /// Send one prepared request, retrying according to the retry policy.
+    fn dispatch_with_retry_sync(&self, prepared: &PreparedRequest) -> Result<SendEmailResponse> {
+        block_on(self.dispatch_with_retry(&Blocking, prepared))
     }
 
     /// Send a single email synchronously.
@@ -161,20 +1470,87 @@ impl LanefulClient {
         self.send(vec![email])
     }
 
+    /// Send the valid emails of a batch synchronously, despite invalid or rejected ones.
+    ///
+    /// Each email is validated first; only the valid ones are sent. If the API rejects
+    /// the request as a whole, e.g. with `400` because of one bad address, it is split in
+    /// halves that are sent again until the rejected emails are isolated, so that one bad
+    /// email does not block the others. Nothing is lost to an error: every email ends up
+    /// in the returned [`BatchOutcome`], by its position in `emails`.
+    ///
+    /// Each request gets its own idempotency key: an explicit key is extended with the
+    /// number of the request, e.g. `key:part-0`. Emails dropped by middleware or the
+    /// recipient policy are isolated like rejected ones and reported as invalid.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use laneful_rs::{Email, LanefulClient};
+    /// # let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+    /// # let emails: Vec<Email> = Vec::new();
+    /// let outcome = client.send_partial(emails);
+    /// for rejected in &outcome.rejected {
+    ///     eprintln!("email {} rejected: {:?}", rejected.index, rejected.reason);
+    /// }
+    /// ```
+    pub fn send_partial(&self, emails: Vec<Email>) -> BatchOutcome {
+        self.send_partial_with_options(emails, &SendOptions::default())
+    }
+
+    /// Send the valid emails of a batch synchronously with per-send options, as
+    /// [`send_partial`](Self::send_partial) does.
+    pub fn send_partial_with_options(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> BatchOutcome {
+        let mut send = PartialSend::new(emails, options);
+        while let Some((part, options)) = send.next() {
+            let result = self.send_with_options(partial::emails(&part), &options);
+            send.record(part, result);
+        }
+        send.finish()
+    }
+
+    /// Send [`Personalized`] emails synchronously in a single request.
+    ///
+    /// The request is written straight from the shared base email, without copying it
+    /// per recipient. Client and profile defaults are merged into the base. With
+    /// middleware, a recipient policy, a router, dry-run mode or a letter opener, the
+    /// emails are [expanded](Personalized::expand) and sent like [`send`](Self::send).
+    pub fn send_personalized(&self, emails: &Personalized) -> Result<SendEmailResponse> {
+        self.send_personalized_with_options(emails, &SendOptions::default())
+    }
+
+    /// Send [`Personalized`] emails synchronously with per-call [`SendOptions`].
+    ///
+    /// See [`send_personalized`](Self::send_personalized).
+    pub fn send_personalized_with_options(
+        &self,
+        emails: &Personalized,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        if self.expands_personalized() {
+            return self.send_with_options(emails.expand(), options);
+        }
+        let prepared = self.prepare_personalized(emails, options)?;
+        self.dispatch_with_retry_sync(&prepared)
+    }
+
     /// Handle the HTTP response for sync calls.
     fn handle_response_sync(
         &self,
         response: reqwest::blocking::Response,
+        attempt: &mut Attempt<'_>,
     ) -> Result<SendEmailResponse> {
         let status = response.status();
+        attempt.response(status, response.headers());
+        self.observe_response(status, response.headers());
 
         if status.is_success() {
             Ok(response.json()?)
         } else {
-            let error_response: ApiErrorResponse = response.json().unwrap_or(ApiErrorResponse {
-                error: format!("HTTP error: {}", status),
-            });
-            Err(LanefulError::ApiError(error_response.error))
+            Err(Self::api_error(status, response.json().ok()))
         }
     }
 
@@ -202,18 +1578,159 @@ impl LanefulClient {
     /// ```
     #[cfg(feature = "async")]
     pub async fn send_async(&self, emails: Vec<Email>) -> Result<SendEmailResponse> {
-        let request = SendEmailRequest { emails };
+        self.send_with_options_async(emails, &SendOptions::default())
+            .await
+    }
 
-        let response = self
-            .async_client
-            .post(self.api_url())
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+    /// Send multiple emails asynchronously with an explicit idempotency key.
+    ///
+    /// See [`send_with_idempotency_key`](Self::send_with_idempotency_key).
+    #[cfg(feature = "async")]
+    pub async fn send_with_idempotency_key_async(
+        &self,
+        emails: Vec<Email>,
+        key: impl Into<String>,
+    ) -> Result<SendEmailResponse> {
+        self.send_with_options_async(emails, &SendOptions::new().idempotency_key(key))
+            .await
+    }
 
-        self.handle_response_async(response).await
+    /// Send multiple emails asynchronously with per-call [`SendOptions`].
+    ///
+    /// See [`send_with_options`](Self::send_with_options).
+    #[cfg(feature = "async")]
+    pub async fn send_with_options_async(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        let result = self.send_prepared_async(emails, options).await;
+        self.after_response(&result);
+        result
+    }
+
+    /// Prepare and dispatch the emails; the part of a send wrapped by middleware.
+    #[cfg(feature = "async")]
+    async fn send_prepared_async(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        if self.dry_run {
+            return self.dry_run_response(emails, options);
+        }
+        if let Some(opener) = &self.letter_opener {
+            return self.capture(opener, emails, options);
+        }
+
+        self.dispatch_all_async(self.prepare(emails, options)?)
+            .await
+    }
+
+    /// Dispatch the prepared requests of a send and combine their responses.
+    #[cfg(feature = "async")]
+    async fn dispatch_all_async(
+        &self,
+        requests: Vec<PreparedRequest>,
+    ) -> Result<SendEmailResponse> {
+        let mut responses = Vec::new();
+        for prepared in requests {
+            let response = self.dispatch_with_retry_async(&prepared).await?;
+            responses.push((response, prepared.positions));
+        }
+        Ok(Self::merge_responses(responses))
+    }
+
+    /// Send a batch of an [`EmailQueue`](crate::EmailQueue), whose emails are matched to
+    /// the results by position.
+    ///
+    /// If the API rejects the content of the request, its emails are handed back as
+    /// prepared, so that the queue can send them one at a time with
+    /// [`send_prepared_email_async`](Self::send_prepared_email_async) without keeping a
+    /// copy of every batch aside. They are only handed back if they went out in a single
+    /// request, so that they line up with the emails passed in. If middleware or the
+    /// recipient policy drop emails, nothing is sent and the emails are handed back as
+    /// passed in, to be sent one at a time so that only the dropped ones fail; a copy is
+    /// kept for this only if the client has middleware or a recipient policy.
+    #[cfg(feature = "queue")]
+    pub(crate) async fn send_batch_async(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> std::result::Result<SendEmailResponse, (LanefulError, Option<Resend>)> {
+        let originals = self.may_drop_emails().then(|| emails.clone());
+        let options = options.clone().keep_positions();
+        let result = if self.dry_run || self.letter_opener.is_some() {
+            self.send_prepared_async(emails, &options)
+                .await
+                .map_err(|err| (err, None))
+        } else {
+            self.send_batch_prepared_async(emails, &options)
+                .await
+                .map_err(|(err, emails)| (err, emails.map(Resend::Prepared)))
+        };
+        let result = match (result, originals) {
+            (Err((err, _)), Some(originals)) if dropped_emails(&err) => {
+                Err((err, Some(Resend::Original(originals))))
+            }
+            (result, _) => result,
+        };
+        for middleware in self.middleware.iter().rev() {
+            middleware.after_response(result.as_ref().map_err(|(err, _)| err));
+        }
+        result
+    }
+
+    #[cfg(feature = "queue")]
+    async fn send_batch_prepared_async(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> std::result::Result<SendEmailResponse, (LanefulError, Option<Vec<Email>>)> {
+        let mut requests = self.prepare(emails, options).map_err(|err| (err, None))?;
+        if requests.len() != 1 {
+            return self
+                .dispatch_all_async(requests)
+                .await
+                .map_err(|err| (err, None));
+        }
+
+        let prepared = requests.remove(0);
+        match self.dispatch_with_retry_async(&prepared).await {
+            Ok(response) => Ok(response),
+            Err(err) if partial::rejects_content(&err) => {
+                let emails = serde_json::from_slice::<SendEmailRequest>(&prepared.body)
+                    .ok()
+                    .map(|request| request.emails);
+                Err((err, emails))
+            }
+            Err(err) => Err((err, None)),
+        }
+    }
+
+    /// Send on its own an email handed back by
+    /// [`send_batch_async`](Self::send_batch_async), without preparing it again.
+    #[cfg(feature = "queue")]
+    pub(crate) async fn send_prepared_email_async(
+        &self,
+        email: Email,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        let result = match self.prepare_requests(vec![email], options) {
+            Ok(requests) => self.dispatch_all_async(requests).await,
+            Err(err) => Err(err),
+        };
+        self.after_response(&result);
+        result
+    }
+
+    /// Send one prepared request, retrying according to the retry policy.
+    #[cfg(feature = "async")]
+    async fn dispatch_with_retry_async(
+        &self,
+        prepared: &PreparedRequest,
+    ) -> Result<SendEmailResponse> {
+        self.dispatch_with_retry(&NonBlocking, prepared).await
     }
 
     /// Send a single email asynchronously.
@@ -224,22 +1741,75 @@ impl LanefulClient {
         self.send_async(vec![email]).await
     }
 
+    /// Send the valid emails of a batch asynchronously, despite invalid or rejected
+    /// ones, as [`send_partial`](Self::send_partial) does.
+    #[cfg(feature = "async")]
+    pub async fn send_partial_async(&self, emails: Vec<Email>) -> BatchOutcome {
+        self.send_partial_with_options_async(emails, &SendOptions::default())
+            .await
+    }
+
+    /// Send the valid emails of a batch asynchronously with per-send options, as
+    /// [`send_partial`](Self::send_partial) does.
+    #[cfg(feature = "async")]
+    pub async fn send_partial_with_options_async(
+        &self,
+        emails: Vec<Email>,
+        options: &SendOptions,
+    ) -> BatchOutcome {
+        let mut send = PartialSend::new(emails, options);
+        while let Some((part, options)) = send.next() {
+            let result = self
+                .send_with_options_async(partial::emails(&part), &options)
+                .await;
+            send.record(part, result);
+        }
+        send.finish()
+    }
+
+    /// Send [`Personalized`] emails asynchronously in a single request.
+    ///
+    /// See [`send_personalized`](Self::send_personalized).
+    #[cfg(feature = "async")]
+    pub async fn send_personalized_async(
+        &self,
+        emails: &Personalized,
+    ) -> Result<SendEmailResponse> {
+        self.send_personalized_with_options_async(emails, &SendOptions::default())
+            .await
+    }
+
+    /// Send [`Personalized`] emails asynchronously with per-call [`SendOptions`].
+    ///
+    /// See [`send_personalized`](Self::send_personalized).
+    #[cfg(feature = "async")]
+    pub async fn send_personalized_with_options_async(
+        &self,
+        emails: &Personalized,
+        options: &SendOptions,
+    ) -> Result<SendEmailResponse> {
+        if self.expands_personalized() {
+            return self.send_with_options_async(emails.expand(), options).await;
+        }
+        let prepared = self.prepare_personalized(emails, options)?;
+        self.dispatch_with_retry_async(&prepared).await
+    }
+
     /// Handle the HTTP response for async calls.
     #[cfg(feature = "async")]
     async fn handle_response_async(
         &self,
         response: reqwest::Response,
+        attempt: &mut Attempt<'_>,
     ) -> Result<SendEmailResponse> {
         let status = response.status();
+        attempt.response(status, response.headers());
+        self.observe_response(status, response.headers());
 
         if status.is_success() {
             Ok(response.json().await?)
         } else {
-            let error_response: ApiErrorResponse =
-                response.json().await.unwrap_or(ApiErrorResponse {
-                    error: format!("HTTP error: {}", status),
-                });
-            Err(LanefulError::ApiError(error_response.error))
+            Err(Self::api_error(status, response.json().await.ok()))
         }
     }
 }
@@ -0,0 +1,275 @@
+//! Client configuration loaded from files and environment variables.
+
+use crate::defaults::EmailDefaults;
+use crate::error::{LanefulError, Result};
+use crate::models::EmailAddress;
+use crate::recipient_policy::RecipientPolicy;
+use crate::retry::{RetryPolicy, option_duration_ms};
+use crate::secret::SecretString;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable naming a configuration file read by [`LanefulConfig::load`].
+pub const CONFIG_ENV: &str = "LANEFUL_CONFIG";
+
+/// Configuration for a [`LanefulClient`](crate::LanefulClient).
+///
+/// Settings are layered: values from a configuration file are overridden by environment
+/// variables, see [`LanefulConfig::load`]. Every setting is optional while loading; the
+/// endpoint and API key are only required when creating a client.
+///
+/// | Setting | Environment variable |
+/// |---|---|
+/// | `endpoint` | `LANEFUL_ENDPOINT` |
+/// | `api_key` | `LANEFUL_API_KEY` |
+/// | `api_key_file` | `LANEFUL_API_KEY_FILE` |
+/// | `timeout_ms` | `LANEFUL_TIMEOUT_MS` |
+/// | `connect_timeout_ms` | `LANEFUL_CONNECT_TIMEOUT_MS` |
+/// | `retry.max_retries` | `LANEFUL_MAX_RETRIES` |
+/// | `defaults.from` | `LANEFUL_DEFAULT_FROM`, `LANEFUL_DEFAULT_FROM_NAME` |
+/// | `defaults.tag` | `LANEFUL_DEFAULT_TAG` |
+/// | `trace_pii` | `LANEFUL_TRACE_PII` |
+/// | `dry_run` | `LANEFUL_DRY_RUN` |
+/// | `letter_opener_dir` | `LANEFUL_LETTER_OPENER_DIR` |
+/// | `redirect_to` | `LANEFUL_REDIRECT_TO` |
+/// | `allowed_recipients` | `LANEFUL_ALLOWED_RECIPIENTS` (comma-separated) |
+///
+/// # Example
+///
+/// A TOML configuration file (requires the `toml` feature):
+///
+/// ```toml
+/// endpoint = "https://custom-endpoint.api.laneful.com"
+/// api_key_file = "/run/secrets/laneful_api_key"
+/// timeout_ms = 10000
+///
+/// [retry]
+/// max_retries = 3
+///
+/// [defaults]
+/// from = { email = "noreply@example.com", name = "Example" }
+/// tag = "transactional"
+/// tracking = { opens = true, clicks = true }
+///
+/// [profiles.marketing]
+/// from = { email = "news@example.com", name = "Example News" }
+/// tag = "marketing"
+/// headers = { "List-Unsubscribe" = "<https://example.com/unsubscribe>" }
+/// ```
+///
+/// ```no_run
+/// use laneful_rs::{LanefulClient, LanefulConfig};
+///
+/// let config = LanefulConfig::from_file("laneful.toml")
+///     .unwrap()
+///     .with_env()
+///     .unwrap();
+/// let client = LanefulClient::from_config(&config).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LanefulConfig {
+    /// Fully qualified base URL of the API.
+    pub endpoint: Option<String>,
+    /// API key. Takes precedence over `api_key_file`.
+    pub api_key: Option<SecretString>,
+    /// File containing the API key, e.g. a mounted container secret.
+    pub api_key_file: Option<PathBuf>,
+    /// Timeout for a whole request.
+    #[serde(rename = "timeout_ms", deserialize_with = "option_duration_ms")]
+    pub timeout: Option<Duration>,
+    /// Timeout for establishing a connection.
+    #[serde(rename = "connect_timeout_ms", deserialize_with = "option_duration_ms")]
+    pub connect_timeout: Option<Duration>,
+    /// Retry policy for transient failures.
+    pub retry: RetryPolicy,
+    /// Defaults applied to every email.
+    pub defaults: EmailDefaults,
+    /// Include recipient addresses and subjects in clear text in traces; see
+    /// [`LanefulClient::trace_pii`](crate::LanefulClient::trace_pii).
+    pub trace_pii: bool,
+    /// Validate and serialize sends without calling the API; see
+    /// [`LanefulClient::dry_run`](crate::LanefulClient::dry_run).
+    pub dry_run: bool,
+    /// Capture sends to this directory instead of calling the API; the endpoint and
+    /// API key are then optional. See [`LetterOpener`](crate::LetterOpener).
+    pub letter_opener_dir: Option<PathBuf>,
+    /// Catch-all address every recipient not in `allowed_recipients` is redirected to.
+    pub redirect_to: Option<String>,
+    /// Allowed recipient domains (`example.com`) and addresses (`dev@example.com`).
+    ///
+    /// Without `redirect_to`, recipients not in this list are dropped; see
+    /// [`RecipientPolicy`].
+    pub allowed_recipients: Vec<String>,
+    /// Named defaults, see [`LanefulClient::profile`](crate::LanefulClient::profile).
+    pub profiles: HashMap<String, EmailDefaults>,
+}
+
+impl LanefulConfig {
+    /// Load the configuration file named by `LANEFUL_CONFIG`, if set, and apply
+    /// environment variable overrides.
+    pub fn load() -> Result<Self> {
+        let config = match std::env::var_os(CONFIG_ENV) {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.with_env()
+    }
+
+    /// Read a configuration file.
+    ///
+    /// The format is chosen by extension: `.json`, `.toml` (requires the `toml` feature)
+    /// or `.yaml`/`.yml` (requires the `yaml` feature).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            LanefulError::ConfigError(format!("cannot read config file {}: {err}", path.display()))
+        })?;
+        let invalid = |err: &dyn std::fmt::Display| {
+            LanefulError::ConfigError(format!("invalid config file {}: {err}", path.display()))
+        };
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents).map_err(|err| invalid(&err)),
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::from_str(&contents).map_err(|err| invalid(&err)),
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|err| invalid(&err)),
+            _ => Err(LanefulError::ConfigError(format!(
+                "unsupported config file format: {} (expected .json, or .toml/.yaml with the \
+                 `toml`/`yaml` feature enabled)",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Override settings with the `LANEFUL_*` environment variables that are set.
+    pub fn with_env(self) -> Result<Self> {
+        self.with_vars(|name| std::env::var(name).ok())
+    }
+
+    fn with_vars(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        if let Some(endpoint) = var("LANEFUL_ENDPOINT") {
+            self.endpoint = Some(endpoint);
+        }
+        if let Some(api_key) = var("LANEFUL_API_KEY") {
+            self.api_key = Some(api_key.into());
+        }
+        if let Some(path) = var("LANEFUL_API_KEY_FILE") {
+            self.api_key_file = Some(path.into());
+        }
+        if let Some(ms) = var("LANEFUL_TIMEOUT_MS") {
+            self.timeout = Some(Duration::from_millis(parse_var("LANEFUL_TIMEOUT_MS", &ms)?));
+        }
+        if let Some(ms) = var("LANEFUL_CONNECT_TIMEOUT_MS") {
+            self.connect_timeout = Some(Duration::from_millis(parse_var(
+                "LANEFUL_CONNECT_TIMEOUT_MS",
+                &ms,
+            )?));
+        }
+        if let Some(retries) = var("LANEFUL_MAX_RETRIES") {
+            self.retry.max_retries = parse_var("LANEFUL_MAX_RETRIES", &retries)?;
+        }
+        if let Some(from) = var("LANEFUL_DEFAULT_FROM") {
+            self.defaults.from = Some(match var("LANEFUL_DEFAULT_FROM_NAME") {
+                Some(name) => EmailAddress::with_name(from, name),
+                None => EmailAddress::new(from),
+            });
+        }
+        if let Some(tag) = var("LANEFUL_DEFAULT_TAG") {
+            self.defaults.tag = Some(tag);
+        }
+        if let Some(address) = var("LANEFUL_REDIRECT_TO") {
+            self.redirect_to = Some(address);
+        }
+        if let Some(allowed) = var("LANEFUL_ALLOWED_RECIPIENTS") {
+            self.allowed_recipients = allowed
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(include) = var("LANEFUL_TRACE_PII") {
+            self.trace_pii = parse_bool("LANEFUL_TRACE_PII", &include)?;
+        }
+        if let Some(enabled) = var("LANEFUL_DRY_RUN") {
+            self.dry_run = parse_bool("LANEFUL_DRY_RUN", &enabled)?;
+        }
+        if let Some(dir) = var("LANEFUL_LETTER_OPENER_DIR") {
+            self.letter_opener_dir = Some(dir.into());
+        }
+        Ok(self)
+    }
+
+    /// The recipient policy described by `redirect_to` and `allowed_recipients`, if any.
+    pub fn recipient_policy(&self) -> Option<RecipientPolicy> {
+        let policy = match &self.redirect_to {
+            Some(address) => RecipientPolicy::redirect_to(address.clone()),
+            None if !self.allowed_recipients.is_empty() => RecipientPolicy::allowlist(),
+            None => return None,
+        };
+        Some(
+            self.allowed_recipients
+                .iter()
+                .fold(policy, |policy, entry| {
+                    if entry.contains('@') {
+                        policy.allow_address(entry.clone())
+                    } else {
+                        policy.allow_domain(entry.clone())
+                    }
+                }),
+        )
+    }
+
+    /// The configured endpoint.
+    pub fn endpoint(&self) -> Result<&str> {
+        self.endpoint.as_deref().ok_or_else(|| {
+            LanefulError::ConfigError(
+                "missing setting `endpoint` (set it in the config file or LANEFUL_ENDPOINT)".into(),
+            )
+        })
+    }
+
+    /// The configured API key, read from `api_key_file` if no key is set directly.
+    pub fn api_key(&self) -> Result<SecretString> {
+        if let Some(api_key) = &self.api_key {
+            return Ok(api_key.clone());
+        }
+
+        let Some(path) = &self.api_key_file else {
+            return Err(LanefulError::ConfigError(
+                "missing setting `api_key` (set `api_key` or `api_key_file` in the config file, \
+                 or LANEFUL_API_KEY or LANEFUL_API_KEY_FILE)"
+                    .into(),
+            ));
+        };
+
+        let contents = SecretString::new(std::fs::read_to_string(path).map_err(|err| {
+            LanefulError::ConfigError(format!(
+                "cannot read api_key_file {}: {err}",
+                path.display()
+            ))
+        })?);
+        Ok(SecretString::new(contents.expose_secret().trim()))
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| LanefulError::ConfigError(format!("{name} must be a number, got {value:?}")))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool> {
+    match value.trim() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(LanefulError::ConfigError(format!(
+            "{name} must be true or false, got {value:?}"
+        ))),
+    }
+}
@@ -0,0 +1,180 @@
+//! Defaults applied to every email sent by a client.
+
+use crate::builder::EmailBuilder;
+use crate::models::{Email, EmailAddress, Tracking};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Values shared by most emails of an application.
+///
+/// Installed with [`LanefulClient::defaults`](crate::LanefulClient::defaults) or as a
+/// named [profile](crate::LanefulClient::profile), defaults are merged into each email
+/// at send time. The email's own values always win:
+///
+/// - `from_header`, `reply_to` and `tag` are used when the email does not set them.
+/// - `tracking` is merged setting by setting: each of `opens`, `clicks`,
+///   `unsubscribes` and `unsubscribe_group_id` comes from the email if set there.
+/// - `headers` are merged by name (case-insensitively): headers set on the email
+///   replace default headers of the same name, other default headers are added.
+///
+/// The sender is required by [`EmailBuilder::build`], so `from` is applied when
+/// creating a builder with [`EmailDefaults::builder`],
+/// [`LanefulClient::email`](crate::LanefulClient::email) or
+/// [`LanefulClient::profile_email`](crate::LanefulClient::profile_email).
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::EmailDefaults;
+///
+/// let defaults = EmailDefaults::new()
+///     .from("noreply@example.com", Some("Example"))
+///     .reply_to("support@example.com", None)
+///     .header("X-Campaign", "welcome")
+///     .tag("transactional");
+///
+/// let email = defaults
+///     .builder()
+///     .to("user@example.com", None)
+///     .subject("Welcome")
+///     .text_content("Hello!")
+///     .build()
+///     .unwrap();
+/// assert_eq!(email.from.email, "noreply@example.com");
+/// assert_eq!(email.tag.as_deref(), Some("transactional"));
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailDefaults {
+    /// Default sender.
+    pub from: Option<EmailAddress>,
+    /// Default visible sender.
+    pub from_header: Option<EmailAddress>,
+    /// Default reply-to address.
+    pub reply_to: Option<EmailAddress>,
+    /// Default custom headers.
+    pub headers: HashMap<String, String>,
+    /// Default tag.
+    pub tag: Option<String>,
+    /// Default tracking settings.
+    pub tracking: Option<Tracking>,
+}
+
+impl EmailDefaults {
+    /// Create empty defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no default is set.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.from.is_none()
+            && self.from_header.is_none()
+            && self.reply_to.is_none()
+            && self.headers.is_empty()
+            && self.tag.is_none()
+            && self.tracking.is_none()
+    }
+
+    /// Set the default sender.
+    pub fn from(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
+        self.from = Some(address(email, name));
+        self
+    }
+
+    /// Set the default visible sender.
+    pub fn from_header(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
+        self.from_header = Some(address(email, name));
+        self
+    }
+
+    /// Set the default reply-to address.
+    pub fn reply_to(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
+        self.reply_to = Some(address(email, name));
+        self
+    }
+
+    /// Add a default custom header.
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the default tag.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Set the default tracking settings.
+    pub fn tracking(mut self, tracking: Tracking) -> Self {
+        self.tracking = Some(tracking);
+        self
+    }
+
+    /// Create an email builder pre-filled with these defaults.
+    pub fn builder(&self) -> EmailBuilder {
+        EmailBuilder::with_defaults(self)
+    }
+
+    /// Layer these defaults over `base`: values set here win, the rest come from `base`.
+    ///
+    /// This is how a [profile](crate::LanefulClient::profile) combines with the
+    /// client's [defaults](crate::LanefulClient::defaults).
+    pub fn layered_over(&self, base: &EmailDefaults) -> EmailDefaults {
+        let mut layered = self.clone();
+        fill(&mut layered.from, &base.from);
+        fill(&mut layered.from_header, &base.from_header);
+        fill(&mut layered.reply_to, &base.reply_to);
+        fill(&mut layered.tag, &base.tag);
+        merge_tracking(&mut layered.tracking, &base.tracking);
+        merge_headers(&mut layered.headers, &base.headers);
+        layered
+    }
+
+    /// Merge these defaults into an email, keeping every value the email sets itself.
+    pub fn apply(&self, email: &mut Email) {
+        fill(&mut email.from_header, &self.from_header);
+        fill(&mut email.reply_to, &self.reply_to);
+        fill(&mut email.tag, &self.tag);
+        merge_tracking(&mut email.tracking, &self.tracking);
+        if !self.headers.is_empty() {
+            merge_headers(email.headers.get_or_insert_default(), &self.headers);
+        }
+    }
+}
+
+fn address(email: impl Into<String>, name: Option<&str>) -> EmailAddress {
+    match name {
+        Some(n) => EmailAddress::with_name(email, n),
+        None => EmailAddress::new(email),
+    }
+}
+
+fn fill<T: Clone>(value: &mut Option<T>, default: &Option<T>) {
+    if value.is_none() {
+        value.clone_from(default);
+    }
+}
+
+fn merge_tracking(tracking: &mut Option<Tracking>, default: &Option<Tracking>) {
+    let Some(default) = default else {
+        return;
+    };
+    let tracking = tracking.get_or_insert_default();
+    fill(&mut tracking.opens, &default.opens);
+    fill(&mut tracking.clicks, &default.clicks);
+    fill(&mut tracking.unsubscribes, &default.unsubscribes);
+    fill(
+        &mut tracking.unsubscribe_group_id,
+        &default.unsubscribe_group_id,
+    );
+}
+
+fn merge_headers(headers: &mut HashMap<String, String>, defaults: &HashMap<String, String>) {
+    for (name, value) in defaults {
+        if !headers.keys().any(|key| key.eq_ignore_ascii_case(name)) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
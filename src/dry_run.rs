@@ -0,0 +1,56 @@
+//! Reports of sends validated and serialized without calling the API.
+
+/// Result of [`LanefulClient::send_dry_run`](crate::LanefulClient::send_dry_run).
+///
+/// Holds one [`DryRunRequest`] per API request the send would make: more than one when
+/// a [router](crate::LanefulClient::router) splits the emails across regions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DryRunReport {
+    /// Requests the send would make, in order.
+    pub requests: Vec<DryRunRequest>,
+}
+
+/// A request that would have been sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRunRequest {
+    /// Exact JSON body of the `SendEmailRequest`.
+    pub body: String,
+    /// Number of emails in the request.
+    pub email_count: usize,
+    /// Number of recipients across `to`, `cc` and `bcc`.
+    pub recipient_count: usize,
+    /// Region the request is pinned to, if any.
+    pub region: Option<String>,
+    /// Idempotency key that would be sent.
+    pub idempotency_key: Option<String>,
+}
+
+impl DryRunRequest {
+    /// Size of the request body in bytes.
+    pub fn size(&self) -> usize {
+        self.body.len()
+    }
+}
+
+impl DryRunReport {
+    /// Total number of emails.
+    pub fn email_count(&self) -> usize {
+        self.requests
+            .iter()
+            .map(|request| request.email_count)
+            .sum()
+    }
+
+    /// Total number of recipients.
+    pub fn recipient_count(&self) -> usize {
+        self.requests
+            .iter()
+            .map(|request| request.recipient_count)
+            .sum()
+    }
+
+    /// Total size of the request bodies in bytes.
+    pub fn size(&self) -> usize {
+        self.requests.iter().map(DryRunRequest::size).sum()
+    }
+}
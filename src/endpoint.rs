@@ -0,0 +1,191 @@
+//! API endpoints, health tracking and region routing.
+
+use crate::error::{LanefulError, Result};
+use crate::models::Email;
+use crate::secret::SecretString;
+use reqwest::header::HeaderValue;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+pub(crate) const DEFAULT_HEALTH_COOL_DOWN: Duration = Duration::from_secs(30);
+
+/// A Laneful API endpoint used by [`LanefulClient::with_endpoints`](crate::LanefulClient::with_endpoints).
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    base_url: String,
+    api_key: Option<SecretString>,
+    region: Option<String>,
+}
+
+impl Endpoint {
+    /// Create an endpoint from its base URL (e.g. `https://eu.api.example.com`).
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            region: None,
+        }
+    }
+
+    /// Use a dedicated API key for this endpoint instead of the client's default key.
+    pub fn api_key(mut self, api_key: impl Into<SecretString>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Assign the endpoint to a region that emails can be pinned to.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Base URL of the endpoint.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Region of the endpoint, if any.
+    pub fn region_name(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+}
+
+/// An endpoint with its resolved API key and health state.
+#[derive(Debug)]
+pub(crate) struct EndpointEntry {
+    pub(crate) endpoint: Endpoint,
+    api_key: SecretString,
+    health: Mutex<Health>,
+}
+
+/// Health state of an endpoint.
+#[derive(Debug, Clone, Copy)]
+enum Health {
+    Healthy,
+    /// Skipped until the given time, or until marked healthy again if the cool-down is
+    /// too long to represent.
+    Unhealthy(Option<Instant>),
+}
+
+impl EndpointEntry {
+    /// `Authorization` header value, flagged as sensitive so it is never logged.
+    pub(crate) fn authorization(&self) -> Result<HeaderValue> {
+        let value = Zeroizing::new(format!("Bearer {}", self.api_key.expose_secret()));
+        let mut header = HeaderValue::from_str(&value).map_err(|_| {
+            LanefulError::ConfigError(
+                "api_key contains characters that are not allowed in an HTTP header".into(),
+            )
+        })?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+
+    /// URL of the send endpoint.
+    pub(crate) fn send_url(&self) -> String {
+        format!("{}/v1/email/send", self.endpoint.base_url)
+    }
+
+    pub(crate) fn is_healthy(&self) -> bool {
+        match *self.health.lock().unwrap_or_else(|e| e.into_inner()) {
+            Health::Healthy => true,
+            Health::Unhealthy(until) => until.is_some_and(|until| until <= Instant::now()),
+        }
+    }
+
+    /// Skip this endpoint (unless nothing else is left) for `cool_down`.
+    pub(crate) fn mark_unhealthy(&self, cool_down: Duration) {
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) =
+            Health::Unhealthy(Instant::now().checked_add(cool_down));
+    }
+
+    pub(crate) fn mark_healthy(&self) {
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) = Health::Healthy;
+    }
+}
+
+/// Ordered endpoints shared by all clones of a client.
+#[derive(Debug)]
+pub(crate) struct EndpointPool {
+    entries: Vec<EndpointEntry>,
+}
+
+impl EndpointPool {
+    pub(crate) fn new(endpoints: Vec<Endpoint>, default_api_key: &SecretString) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(LanefulError::ConfigError(
+                "at least one endpoint is required".into(),
+            ));
+        }
+
+        let entries = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                if endpoint.base_url.is_empty() {
+                    return Err(LanefulError::ConfigError("base_url cannot be empty".into()));
+                }
+
+                let api_key = endpoint
+                    .api_key
+                    .clone()
+                    .unwrap_or_else(|| default_api_key.clone());
+                if api_key.is_empty() {
+                    return Err(LanefulError::ConfigError(format!(
+                        "api_key cannot be empty (endpoint {})",
+                        endpoint.base_url
+                    )));
+                }
+
+                let entry = EndpointEntry {
+                    endpoint,
+                    api_key,
+                    health: Mutex::new(Health::Healthy),
+                };
+                entry.authorization()?;
+                Ok(entry)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    pub(crate) fn entries(&self) -> &[EndpointEntry] {
+        &self.entries
+    }
+
+    /// Endpoints to try for a request, healthy ones first, in configured order.
+    ///
+    /// Requests pinned to a region only ever use endpoints of that region.
+    pub(crate) fn candidates(&self, region: Option<&str>) -> Result<Vec<&EndpointEntry>> {
+        let eligible: Vec<&EndpointEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| region.is_none() || entry.endpoint.region.as_deref() == region)
+            .collect();
+
+        if eligible.is_empty() {
+            return Err(LanefulError::ConfigError(format!(
+                "no endpoint configured for region {}",
+                region.unwrap_or_default()
+            )));
+        }
+
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) =
+            eligible.into_iter().partition(|entry| entry.is_healthy());
+        healthy.extend(unhealthy);
+        Ok(healthy)
+    }
+}
+
+type RouteFn = dyn Fn(&Email) -> Option<String> + Send + Sync;
+
+/// Routing hook that pins emails to a region.
+#[derive(Clone)]
+pub(crate) struct EmailRouter(pub(crate) Arc<RouteFn>);
+
+impl fmt::Debug for EmailRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EmailRouter(..)")
+    }
+}
@@ -3,16 +3,39 @@
 use thiserror::Error;
 
 /// Errors that can occur when using the Laneful SDK.
+///
+/// Error messages never contain API keys or webhook secrets: credentials are only sent
+/// in headers marked as sensitive and are not part of any variant.
+///
+/// The enum is non-exhaustive: some variants only exist with optional features, e.g.
+/// `DatabaseError` with `outbox`, and enabling a feature must not break a `match`
+/// elsewhere in the dependency graph.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum LanefulError {
     /// HTTP request failed.
     #[error("HTTP request failed: {0}")]
     HttpError(#[from] reqwest::Error),
 
     /// API returned an error response.
+    ///
+    /// No longer produced: the client reports error responses as
+    /// [`ApiStatusError`](Self::ApiStatusError), which carries the HTTP status. Code
+    /// matching `ApiError(_)` must match `ApiStatusError { .. }` instead, or use
+    /// [`status`](Self::status) and [`kind`](Self::kind).
+    #[deprecated(note = "API error responses are reported as `ApiStatusError`")]
     #[error("API error: {0}")]
     ApiError(String),
 
+    /// API returned an error response with the given HTTP status.
+    #[error("API error ({status}): {message}")]
+    ApiStatusError {
+        /// HTTP status code of the response.
+        status: u16,
+        /// Error message reported by the API.
+        message: String,
+    },
+
     /// Invalid configuration.
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
@@ -20,6 +43,70 @@ pub enum LanefulError {
     /// Email validation failed.
     #[error("Validation error: {0}")]
     ValidationError(String),
+
+    /// The circuit breaker is open after repeated failures; the request was not sent.
+    #[error("Circuit breaker is open: the Laneful API is considered unavailable")]
+    CircuitOpen,
+
+    /// The send was cancelled before it was made.
+    #[error("Send cancelled")]
+    Cancelled,
+
+    /// Serializing or deserializing a payload failed.
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+
+    /// Reading or writing a file failed.
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// Outbox database operation failed.
+    #[cfg(feature = "outbox")]
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl LanefulError {
+    /// HTTP status code of an API error, if this error came from an API response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::ApiStatusError { status, .. } => Some(*status),
+            Self::HttpError(err) => err.status().map(|status| status.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Short, stable name of the error category, e.g. for metric labels.
+    ///
+    /// One of `timeout`, `connect`, `http`, `rate_limited`, `server`, `client`,
+    /// `config`, `validation`, `circuit_open`, `cancelled`, `serialization`, `io` or
+    /// `database`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::HttpError(err) if err.is_timeout() => "timeout",
+            Self::HttpError(err) if err.is_connect() => "connect",
+            Self::HttpError(_) => "http",
+            Self::ApiStatusError { status: 429, .. } => "rate_limited",
+            Self::ApiStatusError { status, .. } if *status >= 500 => "server",
+            Self::ApiStatusError { .. } => "client",
+            #[allow(deprecated)]
+            Self::ApiError(_) => "client",
+            Self::ConfigError(_) => "config",
+            Self::ValidationError(_) => "validation",
+            Self::CircuitOpen => "circuit_open",
+            Self::Cancelled => "cancelled",
+            Self::SerializationError(_) => "serialization",
+            Self::IoError(_) => "io",
+            #[cfg(feature = "outbox")]
+            Self::DatabaseError(_) => "database",
+        }
+    }
+
+    /// Whether retrying the request may succeed: connection errors, timeouts, `429` and
+    /// `5xx` responses.
+    pub fn is_retryable(&self) -> bool {
+        crate::circuit_breaker::is_outage(self) || self.status() == Some(429)
+    }
 }
 
 /// Result type alias for Laneful operations.
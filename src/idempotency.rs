@@ -0,0 +1,64 @@
+//! Idempotency keys and client-side deduplication of sends.
+
+use crate::models::SendEmailResponse;
+use reqwest::header::HeaderName;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header carrying the idempotency key of a send request.
+pub(crate) const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Derive a stable idempotency key from a serialized request body.
+///
+/// Identical payloads always produce the same key, so retrying the exact same
+/// [`SendEmailRequest`](crate::SendEmailRequest) is recognised as a repeat.
+pub(crate) fn derive_key(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+/// Storage for responses of previously completed sends, keyed by idempotency key.
+///
+/// When a store is configured on the [`LanefulClient`](crate::LanefulClient), a send whose
+/// key is still present returns the stored [`SendEmailResponse`] without calling the API.
+/// Implement this trait to share the cache between processes (e.g. in Redis).
+pub trait IdempotencyStore: fmt::Debug + Send + Sync {
+    /// Look up a stored response that has not expired yet.
+    fn get(&self, key: &str) -> Option<SendEmailResponse>;
+
+    /// Store the response of a successful send for `ttl`.
+    fn put(&self, key: &str, response: &SendEmailResponse, ttl: Duration);
+}
+
+/// In-process [`IdempotencyStore`] backed by a hash map.
+#[derive(Debug, Default)]
+pub struct InMemoryIdempotencyStore {
+    /// Responses by key, with their expiry; `None` for a TTL too long to represent.
+    entries: Mutex<HashMap<String, (Option<Instant>, SendEmailResponse)>>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn get(&self, key: &str) -> Option<SendEmailResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|(expires_at, _)| expires_at.is_none_or(|at| at > Instant::now()))
+            .map(|(_, response)| response.clone())
+    }
+
+    fn put(&self, key: &str, response: &SendEmailResponse, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (expires_at, _)| expires_at.is_none_or(|at| at > now));
+        entries.insert(key.to_string(), (now.checked_add(ttl), response.clone()));
+    }
+}
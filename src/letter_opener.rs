@@ -0,0 +1,378 @@
+//! File-based development transport that captures emails instead of sending them.
+
+use crate::error::Result;
+use crate::mime;
+use crate::models::{Email, EmailAddress, SendEmailRequest};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REQUEST_FILE: &str = "message.json";
+const MESSAGE_FILE: &str = "message.eml";
+const PREVIEW_FILE: &str = "message.html";
+const ATTACHMENTS_DIR: &str = "attachments";
+const INDEX_FILE: &str = "index.html";
+
+/// Serializes index rebuilds, so a rebuild that started earlier cannot replace the page
+/// written by a later one.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+/// Distinguishes the temporary index files of a process.
+static INDEX_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Writes emails to a directory for inspection in a browser, in place of the API.
+///
+/// Each email gets its own directory, named after the capture time, containing:
+///
+/// - `message.json`: the request payload the API would have received,
+/// - `message.eml`: the rendered MIME message, which mail clients can open,
+/// - `message.html`: a preview of the headers and body,
+/// - `attachments/`: the decoded attachments.
+///
+/// An `index.html` page listing every captured email is kept up to date in the
+/// directory; delete it to have it rebuilt, e.g. after removing captured emails.
+///
+/// Install the opener with
+/// [`LanefulClient::letter_opener`](crate::LanefulClient::letter_opener), or set
+/// `letter_opener_dir` in the [configuration](crate::LanefulConfig) to run an application
+/// without Laneful credentials.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{Email, LetterOpener};
+///
+/// let opener = LetterOpener::new("tmp/emails");
+/// let email = Email::builder()
+///     .from("sender@example.com", None)
+///     .to("recipient@example.com", None)
+///     .subject("Hello")
+///     .html_content("<p>Hello, world!</p>")
+///     .build()
+///     .unwrap();
+///
+/// let dir = opener.deliver(&email).unwrap();
+/// println!("open {}", dir.join("message.html").display());
+/// ```
+#[derive(Debug, Clone)]
+pub struct LetterOpener {
+    dir: PathBuf,
+}
+
+impl LetterOpener {
+    /// Capture emails into `dir`, created on first delivery.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Directory emails are captured into.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the index page listing captured emails.
+    pub fn index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_FILE)
+    }
+
+    /// Capture one email, returning the directory it was written to.
+    pub fn deliver(&self, email: &Email) -> Result<PathBuf> {
+        let message = email.to_mime()?;
+        let request = SendEmailRequest {
+            emails: vec![email.clone()],
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        let dir = self.create_message_dir()?;
+        fs::write(dir.join(REQUEST_FILE), serde_json::to_vec_pretty(&request)?)?;
+        fs::write(dir.join(MESSAGE_FILE), message)?;
+
+        let mut attachments = Vec::new();
+        if let Some(list) = &email.attachments
+            && !list.is_empty()
+        {
+            fs::create_dir_all(dir.join(ATTACHMENTS_DIR))?;
+            for attachment in list {
+                let name = unique_file_name(&attachment.file_name, &attachments);
+                fs::write(
+                    dir.join(ATTACHMENTS_DIR).join(&name),
+                    mime::decode_attachment(attachment)?,
+                )?;
+                attachments.push(name);
+            }
+        }
+        fs::write(dir.join(PREVIEW_FILE), preview(email, &attachments))?;
+
+        self.update_index(&dir, email)?;
+        Ok(dir)
+    }
+
+    /// Create a new directory named `<unix millis>-<sequence>`.
+    fn create_message_dir(&self) -> Result<PathBuf> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        for sequence in 0.. {
+            let dir = self.dir.join(format!("{millis:013}-{sequence:04}"));
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(dir),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        unreachable!("unbounded sequence")
+    }
+
+    /// Add a captured message to the index page.
+    ///
+    /// Its row is appended to the page, whose script sorts the rows newest first when it
+    /// is opened, so a delivery costs the same however many emails were captured. The
+    /// page is only rebuilt from the captured messages when it is missing or was not
+    /// written this way.
+    fn update_index(&self, dir: &Path, email: &Email) -> Result<()> {
+        let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if !is_appendable(&self.index_path()) {
+            return self.rebuild_index();
+        }
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        let mut index = OpenOptions::new().append(true).open(self.index_path())?;
+        index.write_all(index_row(&name, email).as_bytes())?;
+        Ok(())
+    }
+
+    /// Regenerate the index page from the captured messages.
+    fn rebuild_index(&self) -> Result<()> {
+        let mut messages = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let Ok(contents) = fs::read(entry.path().join(REQUEST_FILE)) else {
+                continue;
+            };
+            let Ok(request) = serde_json::from_slice::<SendEmailRequest>(&contents) else {
+                continue;
+            };
+            if let Some(email) = request.emails.into_iter().next() {
+                messages.push((entry.file_name().to_string_lossy().into_owned(), email));
+            }
+        }
+        messages.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut page = index_header();
+        for (name, email) in &messages {
+            page.push_str(&index_row(name, email));
+        }
+
+        // Write and rename so a browser never sees a half-written page.
+        let temp = self.dir.join(format!(
+            ".{INDEX_FILE}.{}.{}",
+            std::process::id(),
+            INDEX_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, page)?;
+        fs::rename(&temp, self.index_path())?;
+        Ok(())
+    }
+}
+
+/// Marks an index page that rows can be appended to.
+const INDEX_MARKER: &str = "<!-- laneful letter opener index: rows are appended -->";
+
+/// The index page up to its rows, which are appended after it. Browsers accept the
+/// missing closing tags.
+fn index_header() -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n{INDEX_MARKER}\n<meta charset=\"utf-8\">\n\
+         <title>Captured emails</title>\n<style>{STYLE}</style>\n\
+         <script>document.addEventListener('DOMContentLoaded', () => {{\n\
+         const body = document.getElementById('emails');\n\
+         const rows = [...body.rows].sort((a, b) => b.dataset.name.localeCompare(a.dataset.name));\n\
+         body.append(...rows);\n\
+         document.getElementById('count').textContent = rows.length;\n\
+         for (const t of document.querySelectorAll('time[data-ms]')) \
+         t.textContent = new Date(Number(t.dataset.ms)).toLocaleString();\n\
+         }});</script>\n</head>\n<body>\n\
+         <h1>Captured emails (<span id=\"count\"></span>)</h1>\n<table>\n\
+         <thead><tr><th>Captured</th><th>From</th><th>To</th><th>Subject</th></tr></thead>\n\
+         <tbody id=\"emails\">\n"
+    )
+}
+
+/// Whether the index page exists and rows can be appended to it.
+fn is_appendable(path: &Path) -> bool {
+    let mut head = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(256).read_to_end(&mut head))
+        .is_ok_and(|_| {
+            head.windows(INDEX_MARKER.len())
+                .any(|window| window == INDEX_MARKER.as_bytes())
+        })
+}
+
+/// The index row of the message captured in the directory `name`.
+fn index_row(name: &str, email: &Email) -> String {
+    let millis = name.split('-').next().unwrap_or_default();
+    format!(
+        "<tr data-name=\"{}\"><td><time data-ms=\"{}\">{}</time></td><td>{}</td><td>{}</td>\
+         <td><a href=\"{}/{PREVIEW_FILE}\">{}</a></td></tr>\n",
+        escape(name),
+        escape(millis),
+        escape(name),
+        escape(&addresses(std::slice::from_ref(&email.from))),
+        escape(&addresses(&email.to)),
+        escape(name),
+        escape(&email.subject),
+    )
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse}\
+th,td{text-align:left;padding:.3em .8em;border-bottom:1px solid #ddd;vertical-align:top}\
+iframe{width:100%;height:70vh;border:1px solid #ddd}\
+pre{white-space:pre-wrap;border:1px solid #ddd;padding:1em}";
+
+/// The preview page of one email.
+fn preview(email: &Email, attachments: &[String]) -> String {
+    let mut headers = String::new();
+    let mut row = |name: &str, value: &str| {
+        let _ = writeln!(
+            headers,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(name),
+            escape(value)
+        );
+    };
+    row("From", &addresses(std::slice::from_ref(&email.from)));
+    if let Some(from_header) = &email.from_header {
+        row("From header", &addresses(std::slice::from_ref(from_header)));
+    }
+    row("To", &addresses(&email.to));
+    if let Some(cc) = &email.cc {
+        row("Cc", &addresses(cc));
+    }
+    if let Some(bcc) = &email.bcc {
+        row("Bcc", &addresses(bcc));
+    }
+    if let Some(reply_to) = &email.reply_to {
+        row("Reply-To", &addresses(std::slice::from_ref(reply_to)));
+    }
+    row("Subject", &email.subject);
+    if let Some(tag) = &email.tag {
+        row("Tag", tag);
+    }
+    if let Some(template_id) = &email.template_id {
+        row("Template", template_id);
+    }
+    if let Some(send_time) = email.send_time {
+        row("Send time", &send_time.to_string());
+    }
+    if let Some(custom) = &email.headers {
+        let mut custom: Vec<_> = custom.iter().collect();
+        custom.sort();
+        for (name, value) in custom {
+            row(name, value);
+        }
+    }
+
+    let mut body = String::new();
+    if let Some(html) = &email.html_content {
+        // Inline images reference attachments by `cid:<file name>`.
+        let mut html = html.clone();
+        if let Some(list) = &email.attachments {
+            for (attachment, name) in list.iter().zip(attachments) {
+                html = html.replace(
+                    &format!("cid:{}", attachment.file_name),
+                    &format!("{ATTACHMENTS_DIR}/{name}"),
+                );
+            }
+        }
+        let _ = write!(
+            body,
+            "<h2>HTML</h2>\n<iframe sandbox srcdoc=\"{}\"></iframe>\n",
+            escape(&html)
+        );
+    }
+    if let Some(text) = &email.text_content {
+        let _ = write!(body, "<h2>Text</h2>\n<pre>{}</pre>\n", escape(text));
+    }
+    if let Some(data) = &email.template_data {
+        let _ = write!(
+            body,
+            "<h2>Template data</h2>\n<pre>{}</pre>\n",
+            escape(&serde_json::to_string_pretty(data).unwrap_or_default())
+        );
+    }
+    if !attachments.is_empty() {
+        body.push_str("<h2>Attachments</h2>\n<ul>\n");
+        for name in attachments {
+            let _ = writeln!(
+                body,
+                "<li><a href=\"{ATTACHMENTS_DIR}/{0}\">{0}</a></li>",
+                escape(name)
+            );
+        }
+        body.push_str("</ul>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{STYLE}</style>\n</head>\n<body>\n\
+         <p><a href=\"../{INDEX_FILE}\">All emails</a> · <a href=\"{MESSAGE_FILE}\">{MESSAGE_FILE}</a> · \
+         <a href=\"{REQUEST_FILE}\">{REQUEST_FILE}</a></p>\n<table>\n{headers}</table>\n{body}</body>\n</html>\n",
+        escape(&email.subject)
+    )
+}
+
+fn addresses(addresses: &[EmailAddress]) -> String {
+    addresses
+        .iter()
+        .map(|address| match &address.name {
+            Some(name) if !name.is_empty() => format!("{name} <{}>", address.email),
+            _ => address.email.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A file name safe to write, distinct from the names already `taken`.
+fn unique_file_name(file_name: &str, taken: &[String]) -> String {
+    let mut sanitized: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.trim_start_matches('.').is_empty() {
+        sanitized = format!("attachment{sanitized}");
+    }
+
+    let mut name = sanitized.clone();
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{n}-{sanitized}");
+    }
+    name
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
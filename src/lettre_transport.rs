@@ -0,0 +1,105 @@
+//! `lettre` transports backed by the Laneful HTTP API.
+
+use crate::client::{LanefulClient, SendOptions};
+use crate::error::{LanefulError, Result};
+use crate::models::{Email, SendEmailResponse};
+use lettre::address::Envelope;
+
+/// A [`lettre::Transport`] (and, with the `async` feature, [`lettre::AsyncTransport`])
+/// that sends messages through a [`LanefulClient`].
+///
+/// Code written against `lettre` can switch from SMTP to the HTTP API by swapping the
+/// transport. Messages are converted with [`Email::from_mime_with_envelope`]: recipients
+/// come from the envelope, so `Bcc` recipients are kept, and the `From`, `Subject`,
+/// bodies, attachments and custom headers from the message. Delivery headers such as
+/// `Date` are dropped.
+///
+/// Requires the `lettre` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{LanefulClient, LanefulTransport};
+/// use lettre::{Message, Transport};
+///
+/// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+/// let transport = LanefulTransport::new(client);
+///
+/// let message = Message::builder()
+///     .from("Sender <sender@example.com>".parse().unwrap())
+///     .to("recipient@example.com".parse().unwrap())
+///     .subject("Hello")
+///     .body(String::from("Hello, world!"))
+///     .unwrap();
+///
+/// transport.send(&message).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LanefulTransport {
+    client: LanefulClient,
+    options: SendOptions,
+}
+
+impl LanefulTransport {
+    /// Create a transport sending through `client`.
+    pub fn new(client: LanefulClient) -> Self {
+        Self {
+            client,
+            options: SendOptions::default(),
+        }
+    }
+
+    /// Use `options` for every send, e.g. to select a profile or priority.
+    pub fn options(mut self, options: SendOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The underlying client.
+    pub fn client(&self) -> &LanefulClient {
+        &self.client
+    }
+}
+
+impl From<LanefulClient> for LanefulTransport {
+    fn from(client: LanefulClient) -> Self {
+        Self::new(client)
+    }
+}
+
+/// Convert a formatted `lettre` message into an email.
+fn to_email(envelope: &Envelope, raw: &[u8]) -> Result<Email> {
+    let recipients: Vec<String> = envelope
+        .to()
+        .iter()
+        .map(|address| address.to_string())
+        .collect();
+    let sender = envelope.from().map(|address| address.to_string());
+    let email = Email::from_mime_with_envelope(raw, sender.as_deref(), &recipients)?.email;
+    email.validate()?;
+    Ok(email)
+}
+
+impl lettre::Transport for LanefulTransport {
+    type Ok = SendEmailResponse;
+    type Error = LanefulError;
+
+    fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<SendEmailResponse> {
+        let email = to_email(envelope, email)?;
+        self.client.send_with_options(vec![email], &self.options)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl lettre::AsyncTransport for LanefulTransport {
+    type Ok = SendEmailResponse;
+    type Error = LanefulError;
+
+    async fn send_raw(&self, envelope: &Envelope, email: &[u8]) -> Result<SendEmailResponse> {
+        let email = to_email(envelope, email)?;
+        self.client
+            .send_with_options_async(vec![email], &self.options)
+            .await
+    }
+}
@@ -7,6 +7,20 @@
 //! - **Sync API**: Always available (default)
 //! - **Async API**: Enable with the `async` feature
 //! - **TLS backends**: `native-tls` (default) or `rustls`
+//! - **Partial failures**: Send the valid emails of a batch despite bad ones with
+//!   [`LanefulClient::send_partial`]
+//! - **Bulk sending**: Concurrent sends from a `Stream` of emails with the `bulk` feature
+//! - **Email queue**: Batch emails submitted from anywhere in the background with the
+//!   `queue` feature
+//! - **Transactional outbox**: Enable with `outbox-sqlite` and/or `outbox-postgres`
+//! - **Tracing**: Enable with the `tracing` feature
+//! - **Metrics**: Enable with the `metrics` feature; `otel` propagates trace context
+//! - **Configuration files**: JSON always; TOML and YAML with the `toml` and `yaml` features
+//! - **MIME import**: Parse `.eml` messages with `Email::from_mime` (`mime-parse` feature)
+//! - **lettre**: Send `lettre` messages through the API with the `lettre` feature
+//! - **Letter opener**: Capture emails to files for local development with [`LetterOpener`]
+//! - **Mail merge**: Send one email per row of a JSONL or CSV (`csv` feature) file with
+//!   [`MailMerge`]
 //!
 //! ## Quick Start
 //!
@@ -52,16 +66,69 @@
 //! ```
 
 mod builder;
+#[cfg(feature = "bulk")]
+mod bulk;
+mod circuit_breaker;
 mod client;
+mod config;
+mod defaults;
+mod dry_run;
+mod endpoint;
 mod error;
+mod idempotency;
+mod letter_opener;
+#[cfg(feature = "lettre")]
+mod lettre_transport;
+mod mail_merge;
+mod middleware;
+mod mime;
+#[cfg(feature = "mime-parse")]
+mod mime_import;
 mod models;
+#[cfg(feature = "outbox")]
+mod outbox;
+mod partial;
+mod personalized;
+#[cfg(feature = "queue")]
+mod queue;
+mod rate_limit;
+mod recipient_policy;
+mod retry;
+mod secret;
+mod telemetry;
+mod trace;
 mod webhook;
 
 pub use builder::EmailBuilder;
-pub use client::LanefulClient;
+#[cfg(feature = "bulk")]
+pub use bulk::{BulkHandle, BulkOutcome, BulkOutcomes, BulkSender, BulkState};
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerBuilder, CircuitPermit, CircuitState};
+pub use client::{LanefulClient, SendOptions};
+pub use config::{CONFIG_ENV, LanefulConfig};
+pub use defaults::EmailDefaults;
+pub use dry_run::{DryRunReport, DryRunRequest};
+pub use endpoint::Endpoint;
 pub use error::{LanefulError, Result};
+pub use idempotency::{IdempotencyStore, InMemoryIdempotencyStore};
+pub use letter_opener::LetterOpener;
+#[cfg(feature = "lettre")]
+pub use lettre_transport::LanefulTransport;
+pub use mail_merge::{MailMerge, MergeReport, MergeResult, MergeRow, MergeStatus};
+pub use middleware::Middleware;
+#[cfg(feature = "mime-parse")]
+pub use mime_import::MimeImport;
 pub use models::{
-    ApiErrorResponse, Attachment, Email, EmailAddress, SendEmailRequest, SendEmailResponse,
-    Tracking,
+    ApiErrorResponse, Attachment, Email, EmailAddress, EmailResult, EmailStatus, SendEmailRequest,
+    SendEmailResponse, SendStatus, Tracking,
 };
-pub use webhook::verify_webhook_signature;
\ No newline at end of file
+#[cfg(feature = "outbox")]
+pub use outbox::{Outbox, OutboxDatabase, OutboxEntry, OutboxStats, OutboxStatus, OutboxWorker};
+pub use partial::{BatchOutcome, EmailFailure};
+pub use personalized::{Personalization, Personalized};
+#[cfg(feature = "queue")]
+pub use queue::{EmailQueue, EmailQueueBuilder, QueueResult, QueuedEmail};
+pub use rate_limit::{Priority, RateLimiter, RateLimiterBuilder};
+pub use recipient_policy::{ORIGINAL_RECIPIENTS_HEADER, RecipientPolicy, RecipientReport};
+pub use retry::RetryPolicy;
+pub use secret::SecretString;
+pub use webhook::{WebhookVerifier, verify_webhook_signature};
@@ -0,0 +1,560 @@
+//! Mail merge: one email per row of a CSV or JSONL recipients file.
+
+use crate::builder::EmailBuilder;
+use crate::client::{self, LanefulClient, SendOptions};
+use crate::error::{LanefulError, Result};
+use crate::idempotency;
+use crate::letter_opener::escape;
+use crate::models::{Email, EmailStatus, SendEmailResponse};
+use crate::rate_limit::Priority;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// A row of a recipients file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeRow {
+    /// Position of the row in the file, starting at 1: the data rows of a CSV file (after
+    /// the header) or the non-blank lines of a JSONL file.
+    pub row: usize,
+    /// The row's columns or fields.
+    pub variables: Map<String, Value>,
+}
+
+/// Outcome of a row in the results file of a [`MailMerge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStatus {
+    /// The email was accepted by the API.
+    Sent,
+    /// The row could not be turned into a valid email; it was not sent.
+    Invalid,
+    /// Sending the batch containing the row failed, the API rejected the row's email, or
+    /// middleware or the client's recipient policy dropped it.
+    Failed,
+}
+
+/// A line of the results file of a [`MailMerge`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeResult {
+    /// The row, as in [`MergeRow::row`].
+    pub row: usize,
+    /// The recipient address of the row, if it has one.
+    pub email: String,
+    /// What happened to the row.
+    pub status: MergeStatus,
+    /// Id of the message sent, if the API reported one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Why the row is invalid or failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counts of a [`MailMerge::run`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Rows sent by this run.
+    pub sent: usize,
+    /// Rows skipped because the results file records them as sent by a previous run.
+    pub skipped: usize,
+    /// Rows that are not valid emails.
+    pub invalid: usize,
+    /// Rows whose batch failed or whose email was rejected; running again retries them.
+    pub failed: usize,
+}
+
+impl MergeReport {
+    /// Whether every row has been sent, by this run or a previous one.
+    pub fn is_complete(&self) -> bool {
+        self.invalid == 0 && self.failed == 0
+    }
+}
+
+/// Sends one email per row of a recipients file, personalizing a base email with the
+/// row's variables.
+///
+/// The recipients file is CSV (with a header row; requires the `csv` feature) or JSON
+/// Lines with one object per line, chosen by its `.csv`, `.jsonl` or `.ndjson` extension.
+/// The `email` column holds the recipient's address and the optional `name` column their
+/// name; every column is available as a variable.
+///
+/// - If the base email has a [`template_id`](EmailBuilder::template_id), the row's
+///   variables become its `template_data`, on top of any data set on the base email.
+/// - Otherwise `{{variable}}` placeholders in the subject, text and HTML content are
+///   replaced with the row's values, HTML-escaped in the HTML content. A placeholder
+///   without a matching column makes the row invalid.
+///
+/// Every row is built and validated like [`EmailBuilder::build`]; valid rows are sent in
+/// batches through the client, so its rate limiter, retries and circuit breaker apply.
+/// The outcome of each row is appended to a JSON Lines results file as a
+/// [`MergeResult`]. Running the merge again with the same results file resumes it: rows
+/// already sent are skipped, and invalid and failed rows are tried again. Enable
+/// [`auto_idempotency_keys`](LanefulClient::auto_idempotency_keys) so that a batch cut
+/// short by a crash is recognised by the API when it is sent again.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{Email, LanefulClient, MailMerge};
+///
+/// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key")
+///     .unwrap()
+///     .auto_idempotency_keys(true);
+///
+/// let base = Email::builder()
+///     .from("news@example.com", Some("Example News"))
+///     .subject("Your {{plan}} plan renews soon")
+///     .html_content("<p>Hi {{name}}, your {{plan}} plan renews on {{date}}.</p>");
+///
+/// let report = MailMerge::new(base)
+///     .run(&client, "recipients.jsonl", "results.jsonl")
+///     .unwrap();
+/// println!("{} sent, {} invalid, {} failed", report.sent, report.invalid, report.failed);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MailMerge {
+    base: EmailBuilder,
+    email_column: String,
+    name_column: String,
+    batch_size: usize,
+    options: SendOptions,
+}
+
+impl MailMerge {
+    /// Create a merge of `base`, which holds everything but the recipient.
+    pub fn new(base: EmailBuilder) -> Self {
+        Self {
+            base,
+            email_column: "email".into(),
+            name_column: "name".into(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            options: SendOptions::new().priority(Priority::Bulk),
+        }
+    }
+
+    /// Set the column holding the recipient's address (default: `email`).
+    pub fn email_column(mut self, column: impl Into<String>) -> Self {
+        self.email_column = column.into();
+        self
+    }
+
+    /// Set the column holding the recipient's name (default: `name`).
+    pub fn name_column(mut self, column: impl Into<String>) -> Self {
+        self.name_column = column.into();
+        self
+    }
+
+    /// Set the number of emails sent per request (default: 100).
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Set the options used for every batch (default: the [`Priority::Bulk`] lane).
+    ///
+    /// An [idempotency key](SendOptions::idempotency_key) is extended for each batch
+    /// with a digest of its rows, so that batches are not taken for repeats of one another.
+    pub fn send_options(mut self, options: SendOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read the rows of a CSV or JSONL recipients file.
+    pub fn read_rows(path: impl AsRef<Path>) -> Result<Vec<MergeRow>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("jsonl" | "ndjson") => read_jsonl(path),
+            #[cfg(feature = "csv")]
+            Some("csv") => read_csv(path),
+            #[cfg(not(feature = "csv"))]
+            Some("csv") => Err(LanefulError::ConfigError(
+                "reading CSV recipients requires the `csv` feature".into(),
+            )),
+            _ => Err(LanefulError::ConfigError(format!(
+                "{}: recipients must be a .csv, .jsonl or .ndjson file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Build and validate the email of a row.
+    pub fn email(&self, row: &MergeRow) -> Result<Email> {
+        let address = match row.variables.get(&self.email_column) {
+            Some(Value::String(address)) if !address.trim().is_empty() => address.trim(),
+            _ => {
+                return Err(LanefulError::ValidationError(format!(
+                    "missing `{}` column",
+                    self.email_column
+                )));
+            }
+        };
+        let name = match row.variables.get(&self.name_column) {
+            Some(Value::String(name)) if !name.trim().is_empty() => Some(name.trim()),
+            _ => None,
+        };
+
+        let mut email = self.base.clone().to(address, name).build()?;
+        if email.template_id.is_some() {
+            let mut data = match email.template_data.take() {
+                Some(Value::Object(data)) => data,
+                Some(_) => {
+                    return Err(LanefulError::ValidationError(
+                        "template_data of the base email must be an object".into(),
+                    ));
+                }
+                None => Map::new(),
+            };
+            data.extend(row.variables.clone());
+            email.template_data = Some(Value::Object(data));
+        } else {
+            email.subject = substitute(&email.subject, &row.variables, false)?;
+            if let Some(text) = &email.text_content {
+                email.text_content = Some(substitute(text, &row.variables, false)?);
+            }
+            if let Some(html) = &email.html_content {
+                email.html_content = Some(substitute(html, &row.variables, true)?);
+            }
+            email.validate()?;
+        }
+        Ok(email)
+    }
+
+    /// Check every row of a recipients file without sending anything, returning the
+    /// invalid rows.
+    pub fn validate(&self, recipients: impl AsRef<Path>) -> Result<Vec<MergeResult>> {
+        Ok(Self::read_rows(recipients)?
+            .iter()
+            .filter_map(|row| {
+                let error = self.email(row).err()?;
+                Some(self.result(row, MergeStatus::Invalid, Some(error.to_string())))
+            })
+            .collect())
+    }
+
+    /// Send the merge, recording the outcome of each row in `results` and skipping rows
+    /// it records as sent.
+    ///
+    /// Fails only if the recipients or results file cannot be read or written; invalid
+    /// rows and failed batches are counted in the report.
+    pub fn run(
+        &self,
+        client: &LanefulClient,
+        recipients: impl AsRef<Path>,
+        results: impl AsRef<Path>,
+    ) -> Result<MergeReport> {
+        let (mut run, mut pending) = self.start(recipients.as_ref(), results.as_ref())?;
+        while !pending.is_empty() {
+            let batch = take_batch(&mut pending, self.batch_size);
+            let retry = client.may_drop_emails().then(|| batch.clone());
+            let (rows, emails): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+            let result = client.send_with_options(emails, &self.batch_options(&rows));
+            match (result, retry) {
+                // Send the rows one at a time so that only the dropped ones fail.
+                (Err(err), Some(batch)) if client::dropped_emails(&err) => {
+                    for (row, email) in batch {
+                        let rows = [row];
+                        let result =
+                            client.send_with_options(vec![email], &self.batch_options(&rows));
+                        run.record(&rows, result)?;
+                    }
+                }
+                (result, _) => run.record(&rows, result)?,
+            }
+        }
+        Ok(run.report)
+    }
+
+    /// Async version of [`run`](Self::run).
+    #[cfg(feature = "async")]
+    pub async fn run_async(
+        &self,
+        client: &LanefulClient,
+        recipients: impl AsRef<Path>,
+        results: impl AsRef<Path>,
+    ) -> Result<MergeReport> {
+        let (mut run, mut pending) = self.start(recipients.as_ref(), results.as_ref())?;
+        while !pending.is_empty() {
+            let batch = take_batch(&mut pending, self.batch_size);
+            let retry = client.may_drop_emails().then(|| batch.clone());
+            let (rows, emails): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+            let result = client
+                .send_with_options_async(emails, &self.batch_options(&rows))
+                .await;
+            match (result, retry) {
+                (Err(err), Some(batch)) if client::dropped_emails(&err) => {
+                    for (row, email) in batch {
+                        let rows = [row];
+                        let result = client
+                            .send_with_options_async(vec![email], &self.batch_options(&rows))
+                            .await;
+                        run.record(&rows, result)?;
+                    }
+                }
+                (result, _) => run.record(&rows, result)?,
+            }
+        }
+        Ok(run.report)
+    }
+
+    /// The options of the batch of `rows`.
+    ///
+    /// An idempotency key is narrowed to the rows of the batch: the same rows sent again
+    /// are a repeat, while the next batch, or a batch of a resumed run leaving out rows
+    /// already sent, is not. The send fails if middleware or the recipient policy drop
+    /// emails, as the results would no longer line up with the rows.
+    fn batch_options(&self, rows: &[MergeResult]) -> SendOptions {
+        let rows: Vec<_> = rows.iter().map(|result| result.row.to_string()).collect();
+        let digest = idempotency::derive_key(rows.join(",").as_bytes());
+        self.options
+            .part(format_args!("rows-{}", &digest[..16]))
+            .keep_positions()
+    }
+
+    /// Read the rows and previous results, record invalid rows and return the emails
+    /// left to send.
+    fn start(&self, recipients: &Path, results: &Path) -> Result<(Run, Vec<(MergeResult, Email)>)> {
+        let rows = Self::read_rows(recipients)?;
+        let previous = read_results(results)?;
+        let mut run = Run {
+            path: results.to_path_buf(),
+            report: MergeReport::default(),
+        };
+
+        let mut pending = Vec::new();
+        let mut invalid = Vec::new();
+        for row in &rows {
+            let result = self.result(row, MergeStatus::Sent, None);
+            if let Some(sent) = previous.get(&row.row) {
+                if sent.email != result.email {
+                    return Err(LanefulError::ConfigError(format!(
+                        "{} does not match the recipients: row {} was sent to {}, not {}",
+                        results.display(),
+                        row.row,
+                        sent.email,
+                        result.email
+                    )));
+                }
+                run.report.skipped += 1;
+                continue;
+            }
+            match self.email(row) {
+                Ok(email) => pending.push((result, email)),
+                Err(err) => invalid.push(MergeResult {
+                    status: MergeStatus::Invalid,
+                    error: Some(err.to_string()),
+                    ..result
+                }),
+            }
+        }
+        run.report.invalid = invalid.len();
+        run.append(&invalid)?;
+        Ok((run, pending))
+    }
+
+    fn result(&self, row: &MergeRow, status: MergeStatus, error: Option<String>) -> MergeResult {
+        let email = match row.variables.get(&self.email_column) {
+            Some(Value::String(address)) => address.trim().to_string(),
+            _ => String::new(),
+        };
+        MergeResult {
+            row: row.row,
+            email,
+            status,
+            message_id: None,
+            error,
+        }
+    }
+}
+
+/// State of a merge in progress.
+struct Run {
+    path: PathBuf,
+    report: MergeReport,
+}
+
+impl Run {
+    /// Record the outcome of a batch.
+    fn record(&mut self, rows: &[MergeResult], result: Result<SendEmailResponse>) -> Result<()> {
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                self.report.failed += rows.len();
+                let error = err.to_string();
+                let results: Vec<MergeResult> = rows
+                    .iter()
+                    .map(|row| MergeResult {
+                        status: MergeStatus::Failed,
+                        error: Some(error.clone()),
+                        ..row.clone()
+                    })
+                    .collect();
+                return self.append(&results);
+            }
+        };
+
+        let results: Vec<MergeResult> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| match response.result(index) {
+                Some(result) if result.status == EmailStatus::Rejected => {
+                    self.report.failed += 1;
+                    MergeResult {
+                        status: MergeStatus::Failed,
+                        error: Some(
+                            result
+                                .reason
+                                .clone()
+                                .unwrap_or_else(|| "rejected by the API".into()),
+                        ),
+                        ..row.clone()
+                    }
+                }
+                result => {
+                    self.report.sent += 1;
+                    MergeResult {
+                        status: MergeStatus::Sent,
+                        message_id: result.and_then(|result| result.message_id.clone()),
+                        ..row.clone()
+                    }
+                }
+            })
+            .collect();
+        self.append(&results)
+    }
+
+    /// Append results to the file in a single write.
+    fn append(&self, results: &[MergeResult]) -> Result<()> {
+        if results.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for result in results {
+            lines.push_str(&serde_json::to_string(result)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+fn take_batch(pending: &mut Vec<(MergeResult, Email)>, size: usize) -> Vec<(MergeResult, Email)> {
+    pending.drain(..size.min(pending.len())).collect()
+}
+
+/// The rows recorded as sent in a results file.
+///
+/// A last line cut short by a crash is removed from the file.
+fn read_results(path: &Path) -> Result<HashMap<usize, MergeResult>> {
+    let mut contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err.into()),
+    };
+    if !contents.ends_with('\n') {
+        let complete = contents.rfind('\n').map_or(0, |end| end + 1);
+        contents.truncate(complete);
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(complete as u64)?;
+    }
+
+    let mut sent = HashMap::new();
+    for (n, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result: MergeResult = serde_json::from_str(line).map_err(|err| {
+            LanefulError::ConfigError(format!("{} line {}: {err}", path.display(), n + 1))
+        })?;
+        if result.status == MergeStatus::Sent {
+            sent.insert(result.row, result);
+        } else {
+            sent.remove(&result.row);
+        }
+    }
+    Ok(sent)
+}
+
+fn read_jsonl(path: &Path) -> Result<Vec<MergeRow>> {
+    let mut rows = Vec::new();
+    for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |message: String| {
+            LanefulError::ValidationError(format!("{} line {}: {message}", path.display(), n + 1))
+        };
+        match serde_json::from_str(line).map_err(|err| invalid(err.to_string()))? {
+            Value::Object(variables) => rows.push(MergeRow {
+                row: rows.len() + 1,
+                variables,
+            }),
+            _ => return Err(invalid("expected a JSON object".into())),
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(feature = "csv")]
+fn read_csv(path: &Path) -> Result<Vec<MergeRow>> {
+    let invalid =
+        |err: csv::Error| LanefulError::ValidationError(format!("{}: {err}", path.display()));
+    let mut reader = csv::Reader::from_path(path).map_err(invalid)?;
+    let headers = reader.headers().map_err(invalid)?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(invalid)?;
+        let variables = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| (header.trim().to_string(), Value::String(value.into())))
+            .collect();
+        rows.push(MergeRow {
+            row: rows.len() + 1,
+            variables,
+        });
+    }
+    Ok(rows)
+}
+
+/// Replace `{{variable}}` placeholders with the row's values.
+fn substitute(template: &str, variables: &Map<String, Value>, html: bool) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + end].trim();
+        let value = match variables.get(name) {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Null) => String::new(),
+            Some(value) => value.to_string(),
+            None => {
+                return Err(LanefulError::ValidationError(format!(
+                    "no value for placeholder `{{{{{name}}}}}`"
+                )));
+            }
+        };
+        output.push_str(&rest[..start]);
+        output.push_str(&if html { escape(&value) } else { value });
+        rest = &rest[start + 2 + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
@@ -0,0 +1,57 @@
+//! Request/response hooks run by the client around every send.
+
+use crate::error::{LanefulError, Result};
+use crate::models::{SendEmailRequest, SendEmailResponse};
+use std::fmt;
+
+/// A hook that can inspect and modify requests, and observe their outcome.
+///
+/// Install middleware with [`LanefulClient::middleware`](crate::LanefulClient::middleware).
+/// It runs for both the sync and async APIs:
+///
+/// - [`before_send`](Self::before_send) runs once per send call, after
+///   [defaults](crate::EmailDefaults) are merged and before the request is split by
+///   region, serialized and sent. Middleware runs in the order it was added; returning
+///   an error aborts the send.
+/// - [`after_response`](Self::after_response) runs once per send call with its final
+///   outcome, including errors raised by `before_send`, in reverse order.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::{LanefulError, Middleware, Result, SendEmailRequest};
+///
+/// /// Stamps the tenant id onto every email and blocks suppressed addresses.
+/// #[derive(Debug)]
+/// struct Tenant {
+///     id: String,
+///     suppressed: Vec<String>,
+/// }
+///
+/// impl Middleware for Tenant {
+///     fn before_send(&self, request: &mut SendEmailRequest) -> Result<()> {
+///         for email in &mut request.emails {
+///             if email.to.iter().any(|to| self.suppressed.contains(&to.email)) {
+///                 return Err(LanefulError::ValidationError("recipient is suppressed".into()));
+///             }
+///             email
+///                 .headers
+///                 .get_or_insert_default()
+///                 .insert("X-Tenant".into(), self.id.clone());
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Middleware: fmt::Debug + Send + Sync {
+    /// Inspect or modify a request before it is sent.
+    fn before_send(&self, request: &mut SendEmailRequest) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Observe the outcome of a send.
+    fn after_response(&self, result: std::result::Result<&SendEmailResponse, &LanefulError>) {
+        let _ = result;
+    }
+}
@@ -0,0 +1,439 @@
+//! MIME rendering of emails.
+
+use crate::error::{LanefulError, Result};
+use crate::models::{Attachment, Email, EmailAddress};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Maximum length of a line of encoded content, excluding the line break.
+const LINE_LENGTH: usize = 76;
+/// Maximum length of an RFC 2047 encoded word.
+const ENCODED_WORD_LENGTH: usize = 75;
+/// Header lines longer than this are folded.
+const HEADER_LENGTH: usize = 78;
+
+/// Distinguishes messages rendered in the same millisecond.
+static MESSAGE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+impl Email {
+    /// Render the email as an RFC 5322 message with MIME bodies, e.g. to archive or
+    /// preview what is sent.
+    ///
+    /// The message is structured as recipients' mail clients would receive it:
+    ///
+    /// - text and HTML content form a `multipart/alternative` body,
+    /// - attachments referenced from the HTML as `cid:<file name>` are inline parts of a
+    ///   `multipart/related` body,
+    /// - other attachments are added in a `multipart/mixed` body.
+    ///
+    /// Non-ASCII header values are RFC 2047 encoded, text is quoted-printable and
+    /// attachments are base64. The `Bcc` header is omitted. `Date` is the scheduled
+    /// [send time](Self::send_time), or now; `Date` and `Message-ID` are only added when
+    /// not set in [`headers`](Self::headers). Template emails are rendered by the API, so
+    /// their body only names the template and its data.
+    ///
+    /// Fails with [`LanefulError::ValidationError`] if an attachment is not valid base64,
+    /// a custom header name is invalid, the send time is too far in the future to
+    /// represent, or an email address, attachment file name or content type contains
+    /// control characters such as line breaks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use laneful_rs::Email;
+    ///
+    /// let email = Email::builder()
+    ///     .from("sender@example.com", Some("Sender"))
+    ///     .to("recipient@example.com", None)
+    ///     .subject("Grüße")
+    ///     .text_content("Hello!")
+    ///     .html_content("<p>Hello!</p>")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let message = email.to_mime().unwrap();
+    /// assert!(message.contains("Subject: =?utf-8?B?R3LDvMOfZQ==?=\r\n"));
+    /// assert!(message.contains("Content-Type: multipart/alternative;"));
+    /// ```
+    pub fn to_mime(&self) -> Result<String> {
+        render(self, SystemTime::now())
+    }
+}
+
+/// Decode the base64 content of an attachment.
+pub(crate) fn decode_attachment(attachment: &Attachment) -> Result<Vec<u8>> {
+    let content: String = attachment
+        .content
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    BASE64.decode(content).map_err(|_| {
+        LanefulError::ValidationError(format!(
+            "attachment {} is not valid base64",
+            attachment.file_name
+        ))
+    })
+}
+
+/// A MIME entity: its own headers and body.
+enum Part {
+    Single {
+        headers: String,
+        body: String,
+    },
+    Multipart {
+        subtype: &'static str,
+        parts: Vec<Part>,
+    },
+}
+
+fn render(email: &Email, now: SystemTime) -> Result<String> {
+    let digest = Sha256::digest(serde_json::to_vec(email)?);
+    let id = hex::encode(&digest[..12]);
+
+    let mut message = String::new();
+    let custom = |name: &str| {
+        email
+            .headers
+            .iter()
+            .flatten()
+            .any(|(custom, _)| custom.eq_ignore_ascii_case(name))
+    };
+    if !custom("Date") {
+        let date = match email.send_time {
+            Some(secs) => UNIX_EPOCH
+                .checked_add(Duration::from_secs(secs))
+                .ok_or_else(|| {
+                    LanefulError::ValidationError(format!("send_time {secs} is out of range"))
+                })?,
+            None => now,
+        };
+        header(&mut message, "Date", &format_date(date));
+    }
+    if !custom("Message-ID") {
+        let millis = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let sequence = MESSAGE_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        let domain = email
+            .from
+            .email
+            .rsplit_once('@')
+            .map_or("localhost", |(_, domain)| domain);
+        header(
+            &mut message,
+            "Message-ID",
+            &format!("<{id}.{millis}.{sequence}@{domain}>"),
+        );
+    }
+    header(&mut message, "From", &address(&display_from(email))?);
+    if !email.to.is_empty() {
+        header(&mut message, "To", &address_list(&email.to)?);
+    }
+    if let Some(cc) = email.cc.as_deref().filter(|cc| !cc.is_empty()) {
+        header(&mut message, "Cc", &address_list(cc)?);
+    }
+    if let Some(reply_to) = &email.reply_to {
+        header(&mut message, "Reply-To", &address(reply_to)?);
+    }
+    header(&mut message, "Subject", &encode_words(&email.subject));
+    if let Some(headers) = &email.headers {
+        let mut headers: Vec<_> = headers.iter().collect();
+        headers.sort();
+        for (name, value) in headers {
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':') {
+                return Err(LanefulError::ValidationError(format!(
+                    "invalid header name {name:?}"
+                )));
+            }
+            header(&mut message, name, &encode_words(value));
+        }
+    }
+    header(&mut message, "MIME-Version", "1.0");
+
+    let mut boundaries = 0;
+    write_part(&mut message, &body(email)?, &id, &mut boundaries);
+    Ok(message)
+}
+
+/// The body structure of an email.
+fn body(email: &Email) -> Result<Part> {
+    let text = email.text_content.clone().or_else(|| {
+        email
+            .html_content
+            .is_none()
+            .then(|| template_placeholder(email))
+    });
+    let html = email.html_content.as_deref();
+
+    let mut content = match (text, html) {
+        (Some(text), Some(html)) => Part::Multipart {
+            subtype: "alternative",
+            parts: vec![text_part("text/plain", &text), text_part("text/html", html)],
+        },
+        (Some(text), None) => text_part("text/plain", &text),
+        (None, Some(html)) => text_part("text/html", html),
+        (None, None) => text_part("text/plain", ""),
+    };
+
+    let (inline, attached): (Vec<_>, Vec<_>) =
+        email.attachments.iter().flatten().partition(|attachment| {
+            html.is_some_and(|html| html.contains(&format!("cid:{}", attachment.file_name)))
+        });
+    if !inline.is_empty() {
+        let mut parts = vec![content];
+        for attachment in inline {
+            parts.push(attachment_part(attachment, true)?);
+        }
+        content = Part::Multipart {
+            subtype: "related",
+            parts,
+        };
+    }
+    if !attached.is_empty() {
+        let mut parts = vec![content];
+        for attachment in attached {
+            parts.push(attachment_part(attachment, false)?);
+        }
+        content = Part::Multipart {
+            subtype: "mixed",
+            parts,
+        };
+    }
+    Ok(content)
+}
+
+fn write_part(out: &mut String, part: &Part, id: &str, boundaries: &mut u32) {
+    match part {
+        Part::Single { headers, body } => {
+            let _ = write!(out, "{headers}\r\n{body}");
+        }
+        Part::Multipart { subtype, parts } => {
+            let boundary = format!("=_laneful_{boundaries}_{id}");
+            *boundaries += 1;
+            let _ = write!(
+                out,
+                "Content-Type: multipart/{subtype}; boundary=\"{boundary}\"\r\n\r\n"
+            );
+            for part in parts {
+                let _ = write!(out, "--{boundary}\r\n");
+                write_part(out, part, id, boundaries);
+            }
+            let _ = write!(out, "--{boundary}--\r\n");
+        }
+    }
+}
+
+/// Body of an email rendered from a template by the API.
+fn template_placeholder(email: &Email) -> String {
+    let mut text = format!(
+        "[Rendered by Laneful from template {}]",
+        email.template_id.as_deref().unwrap_or_default()
+    );
+    if let Some(data) = &email.template_data {
+        let _ = write!(
+            text,
+            "\n\nTemplate data:\n{}",
+            serde_json::to_string_pretty(data).unwrap_or_default()
+        );
+    }
+    text
+}
+
+fn text_part(content_type: &str, content: &str) -> Part {
+    Part::Single {
+        headers: format!(
+            "Content-Type: {content_type}; charset=utf-8\r\n\
+             Content-Transfer-Encoding: quoted-printable\r\n"
+        ),
+        body: quoted_printable(content),
+    }
+}
+
+fn attachment_part(attachment: &Attachment, inline: bool) -> Result<Part> {
+    let content = decode_attachment(attachment)?;
+    let content_type = plain_field("attachment content type", &attachment.content_type)?;
+    // Written as is in `Content-ID`.
+    plain_field("attachment file name", &attachment.file_name)?;
+    let file_name = quote(&encode_words(&attachment.file_name));
+    let mut headers = format!(
+        "Content-Type: {content_type}; name={file_name}\r\n\
+         Content-Transfer-Encoding: base64\r\n"
+    );
+    if inline {
+        let _ = write!(
+            headers,
+            "Content-ID: <{}>\r\nContent-Disposition: inline; filename={file_name}\r\n",
+            attachment.file_name
+        );
+    } else {
+        let _ = write!(
+            headers,
+            "Content-Disposition: attachment; filename={file_name}\r\n"
+        );
+    }
+    Ok(Part::Single {
+        headers,
+        body: base64_lines(&content),
+    })
+}
+
+/// Base64 with CRLF line breaks every 76 characters.
+fn base64_lines(content: &[u8]) -> String {
+    let encoded = BASE64.encode(content);
+    let mut lines = String::with_capacity(encoded.len() + encoded.len() / LINE_LENGTH * 2 + 2);
+    for chunk in encoded.as_bytes().chunks(LINE_LENGTH) {
+        lines.push_str(std::str::from_utf8(chunk).expect("base64 is ASCII"));
+        lines.push_str("\r\n");
+    }
+    lines
+}
+
+/// Quoted-printable encoding (RFC 2045) with CRLF line breaks.
+fn quoted_printable(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    for line in text.replace("\r\n", "\n").split('\n') {
+        let bytes = line.as_bytes();
+        let mut length = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            // Trailing whitespace would be stripped in transport, so it is encoded.
+            let literal = matches!(byte, b'!'..=b'<' | b'>'..=b'~')
+                || (matches!(byte, b' ' | b'\t') && i + 1 < bytes.len());
+            let width = if literal { 1 } else { 3 };
+            // Leave room for the `=` of a soft line break.
+            if length + width > LINE_LENGTH - 1 {
+                out.push_str("=\r\n");
+                length = 0;
+            }
+            if literal {
+                out.push(byte as char);
+            } else {
+                let _ = write!(out, "={byte:02X}");
+            }
+            length += width;
+        }
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn header(message: &mut String, name: &str, value: &str) {
+    let _ = write!(message, "{name}: {value}\r\n");
+}
+
+/// The visible sender: `from_header` if set, `from` otherwise.
+fn display_from(email: &Email) -> EmailAddress {
+    email
+        .from_header
+        .clone()
+        .unwrap_or_else(|| email.from.clone())
+}
+
+/// Check that a value written into a header as is cannot break out of it.
+fn plain_field<'a>(field: &str, value: &'a str) -> Result<&'a str> {
+    if value.chars().any(char::is_control) {
+        return Err(LanefulError::ValidationError(format!(
+            "{field} {value:?} contains control characters"
+        )));
+    }
+    Ok(value)
+}
+
+fn address(address: &EmailAddress) -> Result<String> {
+    let email = plain_field("email address", &address.email)?;
+    Ok(match &address.name {
+        Some(name) if !name.is_empty() => {
+            // Line breaks are only safe encoded.
+            let name = if name.is_ascii() && !name.contains(['\r', '\n']) {
+                quote(name)
+            } else {
+                encode_words(name)
+            };
+            format!("{name} <{email}>")
+        }
+        _ => email.to_string(),
+    })
+}
+
+/// Addresses separated by commas, one per line if they do not fit on one.
+fn address_list(addresses: &[EmailAddress]) -> Result<String> {
+    let addresses = addresses.iter().map(address).collect::<Result<Vec<_>>>()?;
+    let joined = addresses.join(", ");
+    Ok(if joined.len() + "Cc: ".len() <= HEADER_LENGTH {
+        joined
+    } else {
+        addresses.join(",\r\n ")
+    })
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Encode a header value as RFC 2047 encoded words if it is not plain ASCII, folding
+/// between words so that no line is too long.
+fn encode_words(value: &str) -> String {
+    if value.is_ascii() && !value.contains(['\r', '\n']) {
+        return value.to_string();
+    }
+
+    // Every 3 bytes become 4 base64 characters.
+    let max_bytes = (ENCODED_WORD_LENGTH - "=?utf-8?B??=".len()) / 4 * 3;
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < value.len() {
+        let mut end = (start + max_bytes).min(value.len());
+        // Never split a character between words.
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        words.push(format!("=?utf-8?B?{}?=", BASE64.encode(&value[start..end])));
+        start = end;
+    }
+    words.join("\r\n ")
+}
+
+/// Format a time as an RFC 5322 date in UTC, e.g. `Tue, 1 Jul 2025 09:30:00 +0000`.
+fn format_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = secs / 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {day} {} {year} {:02}:{:02}:{:02} +0000",
+        // 1970-01-01 was a Thursday.
+        WEEKDAYS[((days + 4) % 7) as usize],
+        MONTHS[month as usize - 1],
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    )
+}
+
+/// Gregorian calendar date of a number of days since 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,334 @@
+//! Parsing of raw MIME messages into emails.
+
+use crate::error::{LanefulError, Result};
+use crate::models::{Attachment, Email, EmailAddress};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use mail_parser::{Address, HeaderName, HeaderValue, MessageParser, MimeHeaders, PartType};
+use std::collections::HashMap;
+
+/// Headers that are part of the message structure and are rebuilt when sending.
+const STRUCTURAL_HEADERS: &[&str] = &[
+    "from",
+    "to",
+    "cc",
+    "bcc",
+    "reply-to",
+    "subject",
+    "mime-version",
+];
+
+/// Headers describing a previous delivery, which do not apply to a new send.
+const TRACE_HEADERS: &[&str] = &[
+    "date",
+    "received",
+    "return-path",
+    "delivered-to",
+    "dkim-signature",
+    "authentication-results",
+    "arc-seal",
+    "arc-message-signature",
+    "arc-authentication-results",
+];
+
+/// An [`Email`] parsed by [`Email::from_mime`], with what could not be carried over.
+#[derive(Debug, Clone)]
+pub struct MimeImport {
+    /// The parsed email.
+    pub email: Email,
+    /// Human-readable descriptions of headers and parts of the message that the email
+    /// cannot represent and were dropped, e.g. `"header Date"`.
+    pub unrepresentable: Vec<String>,
+}
+
+impl MimeImport {
+    /// Whether the whole message was carried over.
+    pub fn is_lossless(&self) -> bool {
+        self.unrepresentable.is_empty()
+    }
+}
+
+impl Email {
+    /// Parse a raw RFC 5322 / MIME message, e.g. a `.eml` file produced by a legacy
+    /// system.
+    ///
+    /// - `From`, `To`, `Cc`, `Bcc`, `Reply-To` and `Subject` map to the email's fields,
+    ///   with RFC 2047 encoded words decoded.
+    /// - The first `text/plain` and `text/html` bodies become the text and HTML content.
+    /// - Attachments and inline parts become [`Attachment`]s. Inline parts referenced from
+    ///   the HTML as `cid:<content id>` are renamed to `cid:<file name>`, the form
+    ///   [`to_mime`](Self::to_mime) and the API use.
+    /// - Other headers are kept in [`headers`](Self::headers).
+    ///
+    /// Anything the email cannot represent, such as delivery headers (`Date`,
+    /// `Received`, signatures), repeated headers or extra bodies, is dropped and listed in
+    /// [`MimeImport::unrepresentable`]. The email is not validated; call
+    /// [`Email::validate`] before sending it.
+    ///
+    /// Requires the `mime-parse` feature. Fails with [`LanefulError::ValidationError`] if
+    /// the message cannot be parsed or has no `From` address.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use laneful_rs::Email;
+    ///
+    /// let raw = b"From: Sender <sender@example.com>\r\n\
+    ///     To: recipient@example.com\r\n\
+    ///     Subject: =?utf-8?B?R3LDvMOfZQ==?=\r\n\
+    ///     Date: Tue, 1 Jul 2025 09:30:00 +0000\r\n\
+    ///     X-Campaign: spring\r\n\
+    ///     \r\n\
+    ///     Hello!\r\n";
+    ///
+    /// let import = Email::from_mime(raw).unwrap();
+    /// assert_eq!(import.email.subject, "Grüße");
+    /// assert_eq!(import.email.text_content.as_deref(), Some("Hello!\r\n"));
+    /// assert_eq!(import.email.headers.unwrap()["X-Campaign"], "spring");
+    /// assert_eq!(import.unrepresentable, vec!["header Date".to_string()]);
+    /// ```
+    pub fn from_mime(raw: &[u8]) -> Result<MimeImport> {
+        parse(raw)
+    }
+
+    /// Parse a raw MIME message as [`from_mime`](Self::from_mime) does, delivering it
+    /// to the recipients of an SMTP envelope rather than those in its headers.
+    ///
+    /// `To` and `Cc` recipients are kept if they are in `recipients`; the other
+    /// recipients are sent as `Bcc`, which is how blind copies travel in SMTP. Header
+    /// recipients missing from the envelope are dropped and reported. A `sender` that
+    /// differs from the `From` header becomes the email's `from`, with the header address
+    /// kept as [`from_header`](Self::from_header).
+    ///
+    /// Requires the `mime-parse` feature.
+    pub fn from_mime_with_envelope(
+        raw: &[u8],
+        sender: Option<&str>,
+        recipients: &[String],
+    ) -> Result<MimeImport> {
+        let mut import = parse(raw)?;
+        let email = &mut import.email;
+
+        let in_envelope = |address: &EmailAddress| {
+            recipients
+                .iter()
+                .any(|recipient| recipient.eq_ignore_ascii_case(&address.email))
+        };
+        let header_count = email.to.len() + email.cc.as_ref().map_or(0, Vec::len);
+        email.to.retain(in_envelope);
+        if let Some(cc) = &mut email.cc {
+            cc.retain(in_envelope);
+        }
+        if email.to.len() + email.cc.as_ref().map_or(0, Vec::len) < header_count {
+            import
+                .unrepresentable
+                .push("To/Cc recipients not in the envelope".into());
+        }
+        if email.cc.as_ref().is_some_and(Vec::is_empty) {
+            email.cc = None;
+        }
+
+        let visible: Vec<String> = email
+            .to
+            .iter()
+            .chain(email.cc.iter().flatten())
+            .map(|address| address.email.to_ascii_lowercase())
+            .collect();
+        let mut bcc = Vec::new();
+        for recipient in recipients {
+            let lower = recipient.to_ascii_lowercase();
+            if !visible.contains(&lower)
+                && !bcc
+                    .iter()
+                    .any(|address: &EmailAddress| address.email.eq_ignore_ascii_case(recipient))
+            {
+                bcc.push(EmailAddress::new(recipient.clone()));
+            }
+        }
+        email.bcc = (!bcc.is_empty()).then_some(bcc);
+
+        if let Some(sender) = sender.filter(|sender| !sender.is_empty())
+            && !sender.eq_ignore_ascii_case(&email.from.email)
+        {
+            email.from_header = Some(std::mem::replace(
+                &mut email.from,
+                EmailAddress::new(sender),
+            ));
+        }
+        Ok(import)
+    }
+}
+
+fn parse(raw: &[u8]) -> Result<MimeImport> {
+    let message = MessageParser::new()
+        .with_mime_headers()
+        .with_address_headers()
+        .default_header_text()
+        .parse(raw)
+        .filter(|message| !message.parts.is_empty())
+        .ok_or_else(|| LanefulError::ValidationError("not a MIME message".into()))?;
+    let mut unrepresentable = Vec::new();
+
+    let from = addresses(message.from(), "From", &mut unrepresentable);
+    if from.len() > 1 {
+        unrepresentable.push("additional From addresses".into());
+    }
+    let from = from
+        .into_iter()
+        .next()
+        .ok_or_else(|| LanefulError::ValidationError("message has no From address".into()))?;
+    let reply_to = addresses(message.reply_to(), "Reply-To", &mut unrepresentable);
+    if reply_to.len() > 1 {
+        unrepresentable.push("additional Reply-To addresses".into());
+    }
+    let to = addresses(message.to(), "To", &mut unrepresentable);
+    let cc = addresses(message.cc(), "Cc", &mut unrepresentable);
+    let bcc = addresses(message.bcc(), "Bcc", &mut unrepresentable);
+
+    let mut headers = HashMap::new();
+    for header in &message.parts[0].headers {
+        let name = header.name();
+        let lower = name.to_ascii_lowercase();
+        if STRUCTURAL_HEADERS.contains(&lower.as_str()) || lower.starts_with("content-") {
+            continue;
+        }
+        if TRACE_HEADERS.contains(&lower.as_str()) {
+            note(&mut unrepresentable, format!("header {name}"));
+            continue;
+        }
+        if headers
+            .keys()
+            .any(|existing: &String| existing.eq_ignore_ascii_case(name))
+        {
+            note(&mut unrepresentable, format!("repeated header {name}"));
+            continue;
+        }
+        // Custom headers are decoded as text; registered ones keep their raw syntax.
+        let value = match (&header.name, header.value()) {
+            (HeaderName::Other(_), HeaderValue::Text(text)) => text.to_string(),
+            _ => unfold(&String::from_utf8_lossy(
+                &raw[header.offset_start as usize..header.offset_end as usize],
+            )),
+        };
+        headers.insert(name.to_string(), value);
+    }
+
+    let mut text_content = None;
+    for &id in &message.text_body {
+        if let Some(part) = message.parts.get(id as usize)
+            && let PartType::Text(text) = &part.body
+        {
+            if text_content.is_none() {
+                text_content = Some(text.to_string());
+            } else {
+                unrepresentable.push("additional text body".into());
+            }
+        }
+    }
+    let mut html_content = None;
+    for &id in &message.html_body {
+        if let Some(part) = message.parts.get(id as usize)
+            && let PartType::Html(html) = &part.body
+        {
+            if html_content.is_none() {
+                html_content = Some(html.to_string());
+            } else {
+                unrepresentable.push("additional HTML body".into());
+            }
+        }
+    }
+
+    let mut attachments = Vec::new();
+    for (n, part) in message.attachments().enumerate() {
+        let file_name = part
+            .attachment_name()
+            .or_else(|| part.content_id())
+            .map_or_else(|| format!("attachment-{}", n + 1), str::to_string);
+        let content_type = part.content_type().map_or_else(
+            || "application/octet-stream".to_string(),
+            |content_type| match content_type.subtype() {
+                Some(subtype) => format!("{}/{subtype}", content_type.ctype()),
+                None => content_type.ctype().to_string(),
+            },
+        );
+        if let (Some(content_id), Some(html)) = (part.content_id(), html_content.as_mut()) {
+            *html = html.replace(&format!("cid:{content_id}"), &format!("cid:{file_name}"));
+        }
+        attachments.push(Attachment::new(
+            file_name,
+            BASE64.encode(part.contents()),
+            content_type,
+        ));
+    }
+
+    if message.parts.iter().any(|part| part.is_encoding_problem) {
+        unrepresentable.push("parts with an unsupported or invalid encoding".into());
+    }
+
+    let email = Email {
+        from,
+        from_header: None,
+        to,
+        subject: message.subject().unwrap_or_default().to_string(),
+        text_content,
+        html_content,
+        reply_to: reply_to.into_iter().next(),
+        cc: (!cc.is_empty()).then_some(cc),
+        bcc: (!bcc.is_empty()).then_some(bcc),
+        attachments: (!attachments.is_empty()).then_some(attachments),
+        headers: (!headers.is_empty()).then_some(headers),
+        template_id: None,
+        template_data: None,
+        send_time: None,
+        webhook_data: None,
+        tag: None,
+        tracking: None,
+    };
+    Ok(MimeImport {
+        email,
+        unrepresentable,
+    })
+}
+
+/// The addresses of an address header, noting entries without an address.
+fn addresses(
+    address: Option<&Address<'_>>,
+    header: &str,
+    unrepresentable: &mut Vec<String>,
+) -> Vec<EmailAddress> {
+    let Some(address) = address else {
+        return Vec::new();
+    };
+    address
+        .iter()
+        .filter_map(|addr| {
+            let Some(email) = addr.address.as_deref() else {
+                note(
+                    unrepresentable,
+                    format!("{header} entries without an address"),
+                );
+                return None;
+            };
+            Some(match addr.name.as_deref() {
+                Some(name) if !name.is_empty() => EmailAddress::with_name(email, name),
+                _ => EmailAddress::new(email),
+            })
+        })
+        .collect()
+}
+
+/// Record an unrepresentable item once.
+fn note(unrepresentable: &mut Vec<String>, item: String) {
+    if !unrepresentable.contains(&item) {
+        unrepresentable.push(item);
+    }
+}
+
+/// Unfold a raw header value onto one line.
+fn unfold(raw: &str) -> String {
+    raw.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
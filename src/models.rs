@@ -1,7 +1,9 @@
 //! Data models for the Laneful Email API.
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
 
 /// An email address with an optional display name.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -141,10 +143,202 @@ pub struct SendEmailRequest {
 }
 
 /// Response from the send email endpoint.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Fields this version of the crate does not know are kept in
+/// [`extra`](Self::extra), so a response stored and serialized again loses nothing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SendEmailResponse {
-    /// Status of the request (e.g., "accepted").
-    pub status: String,
+    /// Status of the request.
+    pub status: SendStatus,
+    /// Outcome of each email, if the API reports them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<EmailResult>,
+    /// Fields not known to this version of the crate.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SendEmailResponse {
+    /// Create a response with the given status and no per-email results.
+    pub fn new(status: SendStatus) -> Self {
+        Self {
+            status,
+            results: Vec::new(),
+            extra: Map::new(),
+        }
+    }
+
+    /// The outcome of the email at `index` among those sent, if reported.
+    pub fn result(&self, index: usize) -> Option<&EmailResult> {
+        self.results.iter().find(|result| result.index == index)
+    }
+
+    /// The outcomes of the emails accepted.
+    pub fn accepted(&self) -> impl Iterator<Item = &EmailResult> {
+        self.results
+            .iter()
+            .filter(|result| result.status == EmailStatus::Accepted)
+    }
+
+    /// The outcomes of the emails rejected.
+    pub fn rejected(&self) -> impl Iterator<Item = &EmailResult> {
+        self.results
+            .iter()
+            .filter(|result| result.status == EmailStatus::Rejected)
+    }
+
+    /// This response narrowed to the email at `index`: its result, if reported, becomes
+    /// the only one, at index 0, and gives the status.
+    #[cfg(any(feature = "bulk", feature = "queue"))]
+    pub(crate) fn for_email(&self, index: usize) -> Self {
+        let Some(result) = self.result(index) else {
+            return Self {
+                status: self.status.clone(),
+                results: Vec::new(),
+                extra: self.extra.clone(),
+            };
+        };
+        Self {
+            status: match &result.status {
+                EmailStatus::Accepted => SendStatus::Accepted,
+                EmailStatus::Rejected => SendStatus::Rejected,
+                EmailStatus::Other(status) => SendStatus::Other(status.clone()),
+            },
+            results: vec![EmailResult {
+                index: 0,
+                ..result.clone()
+            }],
+            extra: self.extra.clone(),
+        }
+    }
+}
+
+/// Status of a send request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum SendStatus {
+    /// The emails were accepted.
+    Accepted,
+    /// Some emails were accepted and others rejected; see
+    /// [`SendEmailResponse::results`].
+    PartiallyAccepted,
+    /// Every email was rejected.
+    Rejected,
+    /// The client is in dry-run mode: the emails were validated but not sent.
+    DryRun,
+    /// The client's letter opener captured the emails instead of sending them.
+    Captured,
+    /// A status this version of the crate does not know.
+    Other(String),
+}
+
+impl SendStatus {
+    /// The status as sent by the API.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::PartiallyAccepted => "partially_accepted",
+            Self::Rejected => "rejected",
+            Self::DryRun => "dry_run",
+            Self::Captured => "captured",
+            Self::Other(status) => status,
+        }
+    }
+}
+
+impl From<String> for SendStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "accepted" => Self::Accepted,
+            "partially_accepted" => Self::PartiallyAccepted,
+            "rejected" => Self::Rejected,
+            "dry_run" => Self::DryRun,
+            "captured" => Self::Captured,
+            _ => Self::Other(status),
+        }
+    }
+}
+
+impl From<SendStatus> for String {
+    fn from(status: SendStatus) -> Self {
+        match status {
+            SendStatus::Other(status) => status,
+            status => status.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SendStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcome of one email of a send request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailResult {
+    /// Position of the email among those sent: its position in the list passed to the
+    /// client, unless middleware or a recipient policy removed emails.
+    pub index: usize,
+    /// Whether the email was accepted.
+    pub status: EmailStatus,
+    /// Id of the message, as reported in webhook events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Why the email was rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Fields not known to this version of the crate.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Status of one email of a send request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum EmailStatus {
+    /// The email was accepted.
+    Accepted,
+    /// The email was rejected; see [`EmailResult::reason`].
+    Rejected,
+    /// A status this version of the crate does not know.
+    Other(String),
+}
+
+impl EmailStatus {
+    /// The status as sent by the API.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+            Self::Other(status) => status,
+        }
+    }
+}
+
+impl From<String> for EmailStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "accepted" => Self::Accepted,
+            "rejected" => Self::Rejected,
+            _ => Self::Other(status),
+        }
+    }
+}
+
+impl From<EmailStatus> for String {
+    fn from(status: EmailStatus) -> Self {
+        match status {
+            EmailStatus::Other(status) => status,
+            status => status.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for EmailStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Error response from the API.
@@ -0,0 +1,694 @@
+//! Transactional outbox for emails.
+//!
+//! Emails are written to the `laneful_outbox` table with [`Outbox::enqueue`], using the
+//! same executor (usually an open transaction) as the business data they belong to. They
+//! only become visible to an [`OutboxWorker`] once that transaction commits, which then
+//! leases pending rows, sends them through [`LanefulClient::send_async`], retries failures
+//! with exponential backoff and dead-letters rows that keep failing or that the API
+//! rejects for good.
+//!
+//! Delivery is at-least-once: if a worker dies after the API accepted an email but before
+//! the row was marked as sent, the row is picked up again once its lease expires. Every
+//! attempt for a row carries the same `Idempotency-Key`, so such repeats can be
+//! deduplicated.
+
+use crate::client::LanefulClient;
+use crate::error::{LanefulError, Result};
+use crate::models::Email;
+use crate::trace;
+use sqlx::{ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Pool, Row, Type};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_BATCH_SIZE: u32 = 50;
+const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_ATTEMPTS: u32 = 8;
+const DEFAULT_BACKOFF_BASE: Duration = Duration::from_secs(5);
+const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(3600);
+/// Longest wait between polls while the database keeps failing.
+const MAX_ERROR_BACKOFF: Duration = Duration::from_secs(60);
+
+const STATUS_PENDING: &str = "pending";
+const STATUS_SENT: &str = "sent";
+const STATUS_DEAD: &str = "dead";
+
+const ENTRY_COLUMNS: &str =
+    "id, payload, status, attempts, available_at, lease_owner, lease_until, last_error, created_at";
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A database that can host the outbox table.
+///
+/// Implemented for `sqlx::Sqlite` (feature `outbox-sqlite`) and `sqlx::Postgres`
+/// (feature `outbox-postgres`).
+pub trait OutboxDatabase: Database + private::Sealed {
+    /// Ordered schema migrations; migration `i` is recorded as version `i + 1`.
+    #[doc(hidden)]
+    const MIGRATIONS: &'static [&'static str];
+
+    #[doc(hidden)]
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+#[cfg(feature = "outbox-sqlite")]
+impl private::Sealed for sqlx::Sqlite {}
+
+#[cfg(feature = "outbox-sqlite")]
+impl OutboxDatabase for sqlx::Sqlite {
+    const MIGRATIONS: &'static [&'static str] = &[
+        "CREATE TABLE IF NOT EXISTS laneful_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts BIGINT NOT NULL DEFAULT 0,
+            available_at BIGINT NOT NULL,
+            lease_owner TEXT,
+            lease_until BIGINT,
+            last_error TEXT,
+            created_at BIGINT NOT NULL,
+            updated_at BIGINT NOT NULL
+        )",
+        "CREATE INDEX IF NOT EXISTS laneful_outbox_status_available
+            ON laneful_outbox (status, available_at)",
+    ];
+
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+#[cfg(feature = "outbox-postgres")]
+impl private::Sealed for sqlx::Postgres {}
+
+#[cfg(feature = "outbox-postgres")]
+impl OutboxDatabase for sqlx::Postgres {
+    const MIGRATIONS: &'static [&'static str] = &[
+        "CREATE TABLE IF NOT EXISTS laneful_outbox (
+            id BIGSERIAL PRIMARY KEY,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts BIGINT NOT NULL DEFAULT 0,
+            available_at BIGINT NOT NULL,
+            lease_owner TEXT,
+            lease_until BIGINT,
+            last_error TEXT,
+            created_at BIGINT NOT NULL,
+            updated_at BIGINT NOT NULL
+        )",
+        "CREATE INDEX IF NOT EXISTS laneful_outbox_status_available
+            ON laneful_outbox (status, available_at)",
+    ];
+
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+/// Lifecycle state of an outbox row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxStatus {
+    /// Waiting to be sent (possibly after a failed attempt).
+    Pending,
+    /// Accepted by the API.
+    Sent,
+    /// Gave up after too many failed attempts.
+    Dead,
+}
+
+impl OutboxStatus {
+    fn parse(status: &str) -> Self {
+        match status {
+            STATUS_SENT => Self::Sent,
+            STATUS_DEAD => Self::Dead,
+            _ => Self::Pending,
+        }
+    }
+}
+
+/// A row of the outbox table.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    /// Row id returned by [`Outbox::enqueue`].
+    pub id: i64,
+    /// Current state of the row.
+    pub status: OutboxStatus,
+    /// Number of send attempts made so far.
+    pub attempts: u32,
+    /// Serialized [`Email`] payload.
+    pub payload: String,
+    /// Earliest time the row may be (re)tried.
+    pub available_at: SystemTime,
+    /// Worker currently holding the lease, if any.
+    pub lease_owner: Option<String>,
+    /// Time the current lease expires, if any.
+    pub lease_until: Option<SystemTime>,
+    /// Error message of the last failed attempt.
+    pub last_error: Option<String>,
+    /// Time the row was enqueued.
+    pub created_at: SystemTime,
+}
+
+impl OutboxEntry {
+    /// Decode the stored email.
+    pub fn email(&self) -> Result<Email> {
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+}
+
+/// Row counts per status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutboxStats {
+    /// Rows waiting to be sent.
+    pub pending: u64,
+    /// Pending rows currently leased by a worker.
+    pub leased: u64,
+    /// Rows accepted by the API.
+    pub sent: u64,
+    /// Dead-lettered rows.
+    pub dead: u64,
+}
+
+/// Handle to the outbox table of a database.
+///
+/// # Example
+///
+/// ```ignore
+/// let outbox = Outbox::new(pool.clone());
+/// outbox.migrate().await?;
+///
+/// let mut tx = pool.begin().await?;
+/// sqlx::query("INSERT INTO orders (id) VALUES ($1)").bind(42).execute(&mut *tx).await?;
+/// outbox.enqueue(&mut *tx, &email).await?;
+/// tx.commit().await?;
+///
+/// outbox.worker(client).run(shutdown_signal()).await?;
+/// ```
+#[derive(Debug)]
+pub struct Outbox<DB: OutboxDatabase> {
+    pool: Pool<DB>,
+}
+
+impl<DB: OutboxDatabase> Clone for Outbox<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<DB> Outbox<DB>
+where
+    DB: OutboxDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'q> i64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> Option<i64>: Encode<'q, DB> + Decode<'q, DB>,
+    for<'q> Option<String>: Encode<'q, DB> + Decode<'q, DB>,
+    for<'q> &'q str: ColumnIndex<DB::Row>,
+    usize: ColumnIndex<DB::Row>,
+{
+    /// Create an outbox on top of a connection pool.
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self { pool }
+    }
+
+    /// The underlying connection pool.
+    pub fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+
+    /// Create or upgrade the outbox schema.
+    ///
+    /// Applied versions are recorded in `laneful_outbox_migrations`, so this is safe to
+    /// call on every startup.
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::query::<DB>(
+            "CREATE TABLE IF NOT EXISTS laneful_outbox_migrations (
+                version BIGINT PRIMARY KEY,
+                applied_at BIGINT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let current: Option<i64> =
+            sqlx::query::<DB>("SELECT MAX(version) FROM laneful_outbox_migrations")
+                .fetch_one(&self.pool)
+                .await?
+                .try_get(0)?;
+        let current = current.unwrap_or(0);
+
+        for (index, migration) in DB::MIGRATIONS.iter().enumerate() {
+            let version = index as i64 + 1;
+            if version <= current {
+                continue;
+            }
+
+            let mut tx = self.pool.begin().await?;
+            sqlx::query::<DB>(migration).execute(&mut *tx).await?;
+            sqlx::query::<DB>(
+                "INSERT INTO laneful_outbox_migrations (version, applied_at) VALUES ($1, $2)",
+            )
+            .bind(version)
+            .bind(now_millis())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Enqueue an email for delivery.
+    ///
+    /// Pass the transaction that writes the related business data (`&mut *tx`) so the
+    /// email is only sent if that transaction commits. Returns the id of the new row.
+    pub async fn enqueue<'c, E>(&self, executor: E, email: &Email) -> Result<i64>
+    where
+        E: Executor<'c, Database = DB>,
+    {
+        let payload = serde_json::to_string(email)?;
+        let now = now_millis();
+
+        let row = sqlx::query::<DB>(
+            "INSERT INTO laneful_outbox (payload, status, attempts, available_at, created_at, updated_at)
+             VALUES ($1, $2, 0, $3, $3, $3)
+             RETURNING id",
+        )
+        .bind(payload)
+        .bind(STATUS_PENDING.to_string())
+        .bind(now)
+        .fetch_one(executor)
+        .await?;
+
+        Ok(row.try_get(0)?)
+    }
+
+    /// Count rows per status.
+    pub async fn stats(&self) -> Result<OutboxStats> {
+        let rows = sqlx::query::<DB>(
+            "SELECT status, COUNT(*), SUM(CASE WHEN lease_until > $1 THEN 1 ELSE 0 END)
+             FROM laneful_outbox GROUP BY status",
+        )
+        .bind(now_millis())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut stats = OutboxStats::default();
+        for row in rows {
+            let status: String = row.try_get(0)?;
+            let count: i64 = row.try_get(1)?;
+            let leased: Option<i64> = row.try_get(2)?;
+            let count = count.max(0) as u64;
+            match OutboxStatus::parse(&status) {
+                OutboxStatus::Pending => {
+                    stats.pending += count;
+                    stats.leased += leased.unwrap_or(0).max(0) as u64;
+                }
+                OutboxStatus::Sent => stats.sent += count,
+                OutboxStatus::Dead => stats.dead += count,
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Pending rows that were enqueued more than `older_than` ago.
+    ///
+    /// These are typically rows that keep failing, rows whose worker died while holding
+    /// the lease, or a sign that no worker is running at all.
+    pub async fn stuck(&self, older_than: Duration) -> Result<Vec<OutboxEntry>> {
+        let cutoff = now_millis() - duration_millis(older_than);
+        let rows = sqlx::query::<DB>(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM laneful_outbox
+             WHERE status = $1 AND created_at < $2 ORDER BY id"
+        ))
+        .bind(STATUS_PENDING.to_string())
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(entry_from_row).collect()
+    }
+
+    /// Dead-lettered rows, oldest first.
+    pub async fn dead_letters(&self, limit: u32) -> Result<Vec<OutboxEntry>> {
+        let rows = sqlx::query::<DB>(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM laneful_outbox WHERE status = $1 ORDER BY id LIMIT $2"
+        ))
+        .bind(STATUS_DEAD.to_string())
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(entry_from_row).collect()
+    }
+
+    /// Fetch a single row by id.
+    pub async fn get(&self, id: i64) -> Result<Option<OutboxEntry>> {
+        let row = sqlx::query::<DB>(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM laneful_outbox WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(entry_from_row).transpose()
+    }
+
+    /// Move a dead-lettered row back to pending with a fresh attempt budget.
+    ///
+    /// Returns `false` if no dead row with that id exists.
+    pub async fn requeue(&self, id: i64) -> Result<bool> {
+        let now = now_millis();
+        let result = sqlx::query::<DB>(
+            "UPDATE laneful_outbox
+             SET status = $1, attempts = 0, available_at = $2, last_error = NULL, updated_at = $2
+             WHERE id = $3 AND status = $4",
+        )
+        .bind(STATUS_PENDING.to_string())
+        .bind(now)
+        .bind(id)
+        .bind(STATUS_DEAD.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(DB::rows_affected(&result) > 0)
+    }
+
+    /// Delete sent rows older than `older_than`. Returns the number of deleted rows.
+    pub async fn purge_sent(&self, older_than: Duration) -> Result<u64> {
+        let cutoff = now_millis() - duration_millis(older_than);
+        let result =
+            sqlx::query::<DB>("DELETE FROM laneful_outbox WHERE status = $1 AND updated_at < $2")
+                .bind(STATUS_SENT.to_string())
+                .bind(cutoff)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(DB::rows_affected(&result))
+    }
+
+    /// Create a worker that drains this outbox through `client`.
+    pub fn worker(&self, client: LanefulClient) -> OutboxWorker<DB> {
+        OutboxWorker {
+            outbox: self.clone(),
+            client,
+            worker_id: default_worker_id(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            lease: DEFAULT_LEASE,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff_base: DEFAULT_BACKOFF_BASE,
+            backoff_max: DEFAULT_BACKOFF_MAX,
+        }
+    }
+}
+
+/// Background worker that sends pending outbox rows.
+///
+/// Several workers (in one or many processes) may drain the same table; rows are
+/// leased so that each one is only processed by a single worker at a time.
+#[derive(Debug)]
+pub struct OutboxWorker<DB: OutboxDatabase> {
+    outbox: Outbox<DB>,
+    client: LanefulClient,
+    worker_id: String,
+    batch_size: u32,
+    lease: Duration,
+    poll_interval: Duration,
+    max_attempts: u32,
+    backoff_base: Duration,
+    backoff_max: Duration,
+}
+
+impl<DB> OutboxWorker<DB>
+where
+    DB: OutboxDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'q> i64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+    for<'q> Option<i64>: Encode<'q, DB> + Decode<'q, DB>,
+    for<'q> Option<String>: Encode<'q, DB> + Decode<'q, DB>,
+    for<'q> &'q str: ColumnIndex<DB::Row>,
+    usize: ColumnIndex<DB::Row>,
+{
+    /// Set the identifier recorded as lease owner (default: process id and start time).
+    pub fn worker_id(mut self, worker_id: impl Into<String>) -> Self {
+        self.worker_id = worker_id.into();
+        self
+    }
+
+    /// Set how many rows are leased per poll (default: 50).
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set how long a leased row stays invisible to other workers (default: 60s).
+    ///
+    /// This must comfortably exceed the time needed to send a whole batch.
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Set how long [`run`](Self::run) sleeps when the outbox is empty (default: 1s).
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set the number of attempts after which a row is dead-lettered (default: 8).
+    ///
+    /// Rows the API rejects with a permanent error, e.g. `400`, are dead-lettered at once.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the exponential backoff between attempts (default: 5s doubling up to 1h).
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff_base = base;
+        self.backoff_max = max;
+        self
+    }
+
+    /// Lease one batch of due rows and try to send each of them.
+    ///
+    /// Returns the number of rows processed.
+    pub async fn run_once(&self) -> Result<usize> {
+        let now = now_millis();
+        let rows = sqlx::query::<DB>(
+            "UPDATE laneful_outbox
+             SET lease_owner = $1, lease_until = $2, updated_at = $3
+             WHERE id IN (
+                 SELECT id FROM laneful_outbox
+                 WHERE status = $4 AND available_at <= $3
+                   AND (lease_until IS NULL OR lease_until < $3)
+                 ORDER BY id
+                 LIMIT $5
+             )
+             AND status = $4 AND (lease_until IS NULL OR lease_until < $3)
+             RETURNING id, payload, attempts, created_at",
+        )
+        .bind(self.worker_id.clone())
+        .bind(now.saturating_add(duration_millis(self.lease)))
+        .bind(now)
+        .bind(STATUS_PENDING.to_string())
+        .bind(i64::from(self.batch_size))
+        .fetch_all(&self.outbox.pool)
+        .await?;
+
+        let mut leased = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let id: i64 = row.try_get(0)?;
+            let payload: String = row.try_get(1)?;
+            let attempts: i64 = row.try_get(2)?;
+            let created_at: i64 = row.try_get(3)?;
+            leased.push((id, payload, attempts.max(0) as u32, created_at));
+        }
+        leased.sort_by_key(|(id, ..)| *id);
+
+        for (id, payload, attempts, created_at) in &leased {
+            let result = match serde_json::from_str::<Email>(payload) {
+                Ok(email) => self
+                    .client
+                    .send_with_idempotency_key_async(
+                        vec![email],
+                        format!("laneful-outbox-{id}-{created_at}"),
+                    )
+                    .await
+                    .map(|_| ()),
+                Err(err) => Err(LanefulError::from(err)),
+            };
+
+            match result {
+                Ok(()) => self.mark_sent(*id).await?,
+                Err(err) => self.mark_failed(*id, attempts + 1, &err).await?,
+            }
+        }
+
+        Ok(leased.len())
+    }
+
+    /// Process the outbox until `shutdown` resolves.
+    ///
+    /// The shutdown signal is only observed between batches, so an in-flight batch is
+    /// always finished (or left to its lease) cleanly. Database errors, e.g. a lost
+    /// connection, do not stop the worker: it backs off and polls again.
+    pub async fn run<F>(&self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let mut errors = 0;
+
+        loop {
+            let processed = match self.run_once().await {
+                Ok(processed) => {
+                    errors = 0;
+                    processed
+                }
+                Err(err) => {
+                    errors += 1;
+                    let delay = self.error_backoff(errors);
+                    trace::outbox_poll_failed(errors, delay, &err);
+                    tokio::select! {
+                        _ = &mut shutdown => return Ok(()),
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                    continue;
+                }
+            };
+
+            if processed < self.batch_size as usize {
+                tokio::select! {
+                    _ = &mut shutdown => return Ok(()),
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                }
+            } else {
+                tokio::select! {
+                    biased;
+                    _ = &mut shutdown => return Ok(()),
+                    _ = std::future::ready(()) => {}
+                }
+            }
+        }
+    }
+
+    async fn mark_sent(&self, id: i64) -> Result<()> {
+        sqlx::query::<DB>(
+            "UPDATE laneful_outbox
+             SET status = $1, attempts = attempts + 1, lease_owner = NULL, lease_until = NULL,
+                 last_error = NULL, updated_at = $2
+             WHERE id = $3 AND lease_owner = $4",
+        )
+        .bind(STATUS_SENT.to_string())
+        .bind(now_millis())
+        .bind(id)
+        .bind(self.worker_id.clone())
+        .execute(&self.outbox.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: i64, attempts: u32, err: &LanefulError) -> Result<()> {
+        let (status, retry_in) = if is_permanent(err) || attempts >= self.max_attempts {
+            (STATUS_DEAD, Duration::ZERO)
+        } else {
+            (STATUS_PENDING, self.backoff_for(attempts))
+        };
+        let now = now_millis();
+
+        sqlx::query::<DB>(
+            "UPDATE laneful_outbox
+             SET status = $1, attempts = $2, available_at = $3, last_error = $4,
+                 lease_owner = NULL, lease_until = NULL, updated_at = $5
+             WHERE id = $6 AND lease_owner = $7",
+        )
+        .bind(status.to_string())
+        .bind(i64::from(attempts))
+        .bind(now.saturating_add(duration_millis(retry_in)))
+        .bind(err.to_string())
+        .bind(now)
+        .bind(id)
+        .bind(self.worker_id.clone())
+        .execute(&self.outbox.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    fn backoff_for(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff_base
+            .saturating_mul(factor)
+            .min(self.backoff_max)
+    }
+
+    /// Wait after `errors` consecutive failed polls.
+    fn error_backoff(&self, errors: u32) -> Duration {
+        let factor = 2u32.saturating_pow(errors.saturating_sub(1));
+        self.poll_interval
+            .saturating_mul(factor)
+            .min(MAX_ERROR_BACKOFF.max(self.poll_interval))
+    }
+}
+
+/// Whether sending a row again cannot succeed: its payload cannot be decoded, or the API
+/// rejected it for good. An open circuit breaker is an outage, not a rejection.
+fn is_permanent(err: &LanefulError) -> bool {
+    !err.is_retryable() && !matches!(err, LanefulError::CircuitOpen)
+}
+
+fn entry_from_row<DB>(row: &DB::Row) -> Result<OutboxEntry>
+where
+    DB: Database,
+    for<'q> i64: Decode<'q, DB> + Type<DB>,
+    for<'q> String: Decode<'q, DB> + Type<DB>,
+    for<'q> Option<i64>: Decode<'q, DB>,
+    for<'q> Option<String>: Decode<'q, DB>,
+    for<'q> &'q str: ColumnIndex<DB::Row>,
+{
+    let status: String = row.try_get("status")?;
+    let attempts: i64 = row.try_get("attempts")?;
+    let lease_until: Option<i64> = row.try_get("lease_until")?;
+
+    Ok(OutboxEntry {
+        id: row.try_get("id")?,
+        status: OutboxStatus::parse(&status),
+        attempts: attempts.max(0) as u32,
+        payload: row.try_get("payload")?,
+        available_at: from_millis(row.try_get("available_at")?),
+        lease_owner: row.try_get("lease_owner")?,
+        lease_until: lease_until.map(from_millis),
+        last_error: row.try_get("last_error")?,
+        created_at: from_millis(row.try_get("created_at")?),
+    })
+}
+
+fn default_worker_id() -> String {
+    format!("{}-{}", std::process::id(), now_millis())
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(duration_millis)
+        .unwrap_or(0)
+}
+
+fn duration_millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
@@ -0,0 +1,180 @@
+//! Sends that deliver the valid emails of a batch despite bad ones.
+
+use crate::client::SendOptions;
+use crate::error::{LanefulError, Result};
+use crate::models::{Email, EmailResult, EmailStatus, SendEmailResponse};
+use serde_json::Map;
+use std::sync::Arc;
+
+/// Outcome of [`LanefulClient::send_partial`](crate::LanefulClient::send_partial), by
+/// position of each email in the list passed to it.
+///
+/// Every email ends up in exactly one of the lists, each sorted by index.
+#[derive(Debug, Clone, Default)]
+pub struct BatchOutcome {
+    /// Emails accepted by the API, with their message ids if it reported them.
+    pub accepted: Vec<EmailResult>,
+    /// Emails that failed validation; they were not sent.
+    pub invalid: Vec<EmailFailure>,
+    /// Emails the API rejected, with the reason.
+    pub rejected: Vec<EmailResult>,
+    /// Emails whose request failed for another reason, e.g. the API being unavailable;
+    /// sending them again may succeed. The emails of a request share its error.
+    pub failed: Vec<EmailFailure>,
+}
+
+impl BatchOutcome {
+    /// Whether every email was accepted.
+    pub fn is_complete(&self) -> bool {
+        self.invalid.is_empty() && self.rejected.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// An email of a [`BatchOutcome`] that was not sent.
+#[derive(Debug, Clone)]
+pub struct EmailFailure {
+    /// Position of the email in the list passed to the client.
+    pub index: usize,
+    /// Why it was not sent.
+    pub error: Arc<LanefulError>,
+}
+
+/// Emails of a partial send still to be sent, with their positions.
+pub(crate) type Part = Vec<(usize, Email)>;
+
+/// State of a partial send: the parts still to be sent and the outcome so far.
+///
+/// A part rejected as a whole by the API is split in halves that are sent on their own,
+/// until the emails causing the rejection are isolated.
+pub(crate) struct PartialSend {
+    pending: Vec<Part>,
+    outcome: BatchOutcome,
+    options: SendOptions,
+    /// Number of parts handed out so far.
+    sent: usize,
+}
+
+impl PartialSend {
+    /// Validate the emails, setting aside the invalid ones.
+    pub(crate) fn new(emails: Vec<Email>, options: &SendOptions) -> Self {
+        let mut outcome = BatchOutcome::default();
+        let mut valid = Vec::with_capacity(emails.len());
+        for (index, email) in emails.into_iter().enumerate() {
+            match email.validate() {
+                Ok(()) => valid.push((index, email)),
+                Err(err) => outcome.invalid.push(EmailFailure {
+                    index,
+                    error: Arc::new(err),
+                }),
+            }
+        }
+        Self {
+            pending: if valid.is_empty() {
+                Vec::new()
+            } else {
+                vec![valid]
+            },
+            outcome,
+            options: options.clone().keep_positions(),
+            sent: 0,
+        }
+    }
+
+    /// The next part to send, with its options.
+    ///
+    /// Each part gets its own idempotency key, or the API and the client's idempotency
+    /// store would take it for a repeat of the first part. Parts fail if emails are
+    /// dropped on the way, which would shift the positions of the results.
+    pub(crate) fn next(&mut self) -> Option<(Part, SendOptions)> {
+        let part = self.pending.pop()?;
+        let options = self.options.part(format_args!("part-{}", self.sent));
+        self.sent += 1;
+        Some((part, options))
+    }
+
+    /// Record the result of sending `part`.
+    pub(crate) fn record(&mut self, part: Part, result: Result<SendEmailResponse>) {
+        match result {
+            Ok(response) => {
+                for (position, (index, _)) in part.into_iter().enumerate() {
+                    let result = match response.result(position) {
+                        Some(result) => EmailResult {
+                            index,
+                            ..result.clone()
+                        },
+                        None => EmailResult {
+                            index,
+                            status: EmailStatus::Accepted,
+                            message_id: None,
+                            reason: None,
+                            extra: Map::new(),
+                        },
+                    };
+                    if result.status == EmailStatus::Rejected {
+                        self.outcome.rejected.push(result);
+                    } else {
+                        self.outcome.accepted.push(result);
+                    }
+                }
+            }
+            Err(err) if rejects_content(&err) && part.len() > 1 => {
+                let mut first = part;
+                let second = first.split_off(first.len() / 2);
+                // Sent last-in first-out: keep the order of the emails.
+                self.pending.push(second);
+                self.pending.push(first);
+            }
+            Err(err) if rejects_content(&err) => {
+                let (index, _) = part.into_iter().next().expect("parts are never empty");
+                if matches!(err, LanefulError::ValidationError(_)) {
+                    self.outcome.invalid.push(EmailFailure {
+                        index,
+                        error: Arc::new(err),
+                    });
+                } else {
+                    self.outcome.rejected.push(EmailResult {
+                        index,
+                        status: EmailStatus::Rejected,
+                        message_id: None,
+                        reason: Some(err.to_string()),
+                        extra: Map::new(),
+                    });
+                }
+            }
+            Err(err) => {
+                let error = Arc::new(err);
+                self.outcome
+                    .failed
+                    .extend(part.into_iter().map(|(index, _)| EmailFailure {
+                        index,
+                        error: error.clone(),
+                    }));
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> BatchOutcome {
+        let outcome = &mut self.outcome;
+        outcome.accepted.sort_by_key(|result| result.index);
+        outcome.invalid.sort_by_key(|failure| failure.index);
+        outcome.rejected.sort_by_key(|result| result.index);
+        outcome.failed.sort_by_key(|failure| failure.index);
+        self.outcome
+    }
+}
+
+/// The emails of a part, to be sent.
+pub(crate) fn emails(part: &Part) -> Vec<Email> {
+    part.iter().map(|(_, email)| email.clone()).collect()
+}
+
+/// Whether an error rejects the content of a request, as opposed to the credentials
+/// or the availability of the API.
+pub(crate) fn rejects_content(err: &LanefulError) -> bool {
+    match err {
+        LanefulError::ValidationError(_) => true,
+        _ => err.status().is_some_and(|status| {
+            (400..500).contains(&status) && ![401, 403, 408, 429].contains(&status)
+        }),
+    }
+}
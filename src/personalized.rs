@@ -0,0 +1,342 @@
+//! Per-recipient variations of one email, sent in a single request.
+
+use crate::builder::{
+    EmailBuilder, validate_content, validate_recipient_count, validate_webhook_data,
+};
+use crate::error::Result;
+use crate::models::{Attachment, Email, EmailAddress, Tracking};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A recipient of a [`Personalized`] email and what is specific to them.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::{EmailAddress, Personalization};
+/// use serde_json::json;
+///
+/// let recipient = Personalization::new(EmailAddress::with_name("ann@example.com", "Ann"))
+///     .template_data(json!({ "name": "Ann", "plan": "pro" }))
+///     .webhook_data("customer_id", "42");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Personalization {
+    pub(crate) to: Vec<EmailAddress>,
+    template_data: Option<Value>,
+    webhook_data: HashMap<String, String>,
+}
+
+impl Personalization {
+    /// Create a personalization sending to `recipient`.
+    pub fn new(recipient: impl Into<EmailAddress>) -> Self {
+        Self {
+            to: vec![recipient.into()],
+            template_data: None,
+            webhook_data: HashMap::new(),
+        }
+    }
+
+    /// Add another `to` recipient sharing this personalization.
+    pub fn to(mut self, recipient: impl Into<EmailAddress>) -> Self {
+        self.to.push(recipient.into());
+        self
+    }
+
+    /// Set the template data of this recipient.
+    ///
+    /// If both this and the base email's data are objects, the two are merged, with
+    /// this recipient's values taking precedence; otherwise this data replaces the base's.
+    pub fn template_data(mut self, data: Value) -> Self {
+        self.template_data = Some(data);
+        self
+    }
+
+    /// Add a webhook data entry, overriding the base email's entry with the same key.
+    pub fn webhook_data(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.webhook_data.insert(key.into(), value.into());
+        self
+    }
+
+    /// The `to` recipients.
+    pub fn recipients(&self) -> &[EmailAddress] {
+        &self.to
+    }
+
+    /// The template data of this recipient merged over that of `base`.
+    fn merged_template_data<'a>(&'a self, base: &'a Email) -> Option<Cow<'a, Value>> {
+        match (&base.template_data, &self.template_data) {
+            (Some(Value::Object(base)), Some(Value::Object(own))) if !base.is_empty() => {
+                let mut merged = base.clone();
+                merged.extend(own.iter().map(|(key, value)| (key.clone(), value.clone())));
+                Some(Cow::Owned(Value::Object(merged)))
+            }
+            (_, Some(own)) => Some(Cow::Borrowed(own)),
+            (base, None) => base.as_ref().map(Cow::Borrowed),
+        }
+    }
+
+    /// The webhook data of this recipient merged over that of `base`.
+    fn merged_webhook_data<'a>(
+        &'a self,
+        base: &'a Email,
+    ) -> Option<Cow<'a, HashMap<String, String>>> {
+        match &base.webhook_data {
+            _ if self.webhook_data.is_empty() => base.webhook_data.as_ref().map(Cow::Borrowed),
+            Some(base) if !base.is_empty() => {
+                let mut merged = base.clone();
+                merged.extend(
+                    self.webhook_data
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone())),
+                );
+                Some(Cow::Owned(merged))
+            }
+            _ => Some(Cow::Borrowed(&self.webhook_data)),
+        }
+    }
+}
+
+/// One email sent to many recipients, each with their own template and webhook data.
+///
+/// Sending personalized emails as a `Vec<Email>` copies the content, attachments and
+/// headers once per recipient. A `Personalized` keeps a single base email behind an
+/// [`Arc`] and writes each recipient's email straight into the request body, so large
+/// HTML bodies and attachments are never cloned.
+///
+/// Every recipient gets the base email with its `to` replaced by the recipient's, and
+/// its template and webhook data merged with theirs; the base's `cc` and `bcc`, if any,
+/// are kept on every email. Send it with
+/// [`LanefulClient::send_personalized`](crate::LanefulClient::send_personalized), or
+/// serialize it: it serializes as the list of expanded emails.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{Email, LanefulClient, Personalization, Personalized};
+/// use serde_json::json;
+///
+/// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+///
+/// let base = Email::builder()
+///     .from("news@example.com", Some("Example News"))
+///     .subject("Your monthly report")
+///     .template_id("monthly-report");
+///
+/// let emails = Personalized::from_builder(base)
+///     .unwrap()
+///     .recipient(Personalization::new("ann@example.com").template_data(json!({ "name": "Ann" })))
+///     .recipient(Personalization::new("bob@example.com").template_data(json!({ "name": "Bob" })));
+///
+/// client.send_personalized(&emails).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Personalized {
+    base: Arc<Email>,
+    recipients: Vec<Personalization>,
+}
+
+impl Personalized {
+    /// Create personalized emails from a base email, shared rather than copied.
+    ///
+    /// The base's `to` recipients are ignored; add recipients with
+    /// [`recipient`](Self::recipient).
+    pub fn new(base: impl Into<Arc<Email>>) -> Self {
+        Self {
+            base: base.into(),
+            recipients: Vec::new(),
+        }
+    }
+
+    /// Create personalized emails from a builder holding everything but the recipients.
+    ///
+    /// Fails with [`LanefulError::ValidationError`](crate::LanefulError::ValidationError)
+    /// if the sender, subject or content is missing or the tag is too long.
+    pub fn from_builder(base: EmailBuilder) -> Result<Self> {
+        let base = base.assemble()?;
+        validate_content(&base)?;
+        Ok(Self::new(base))
+    }
+
+    /// Add a recipient.
+    pub fn recipient(mut self, recipient: Personalization) -> Self {
+        self.recipients.push(recipient);
+        self
+    }
+
+    /// Add several recipients.
+    pub fn recipients(mut self, recipients: impl IntoIterator<Item = Personalization>) -> Self {
+        self.recipients.extend(recipients);
+        self
+    }
+
+    /// The base email.
+    pub fn base(&self) -> &Arc<Email> {
+        &self.base
+    }
+
+    /// The recipients' personalizations.
+    pub fn personalizations(&self) -> &[Personalization] {
+        &self.recipients
+    }
+
+    /// Number of emails, one per recipient.
+    pub fn len(&self) -> usize {
+        self.recipients.len()
+    }
+
+    /// Whether there are no recipients.
+    pub fn is_empty(&self) -> bool {
+        self.recipients.is_empty()
+    }
+
+    /// Check every expanded email against the API's limits, as [`Email::validate`] does.
+    pub fn validate(&self) -> Result<()> {
+        self.expanded().validate()
+    }
+
+    /// Expand into one [`Email`] per recipient, copying the base email for each.
+    pub fn expand(&self) -> Vec<Email> {
+        self.expanded().expand()
+    }
+
+    pub(crate) fn expanded(&self) -> Expanded<'_> {
+        Expanded {
+            base: &self.base,
+            recipients: &self.recipients,
+        }
+    }
+}
+
+impl Serialize for Personalized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.expanded().serialize(serializer)
+    }
+}
+
+/// Personalizations over a base email, which the client may have merged defaults into.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Expanded<'a> {
+    pub(crate) base: &'a Email,
+    pub(crate) recipients: &'a [Personalization],
+}
+
+/// Request body of personalized emails, serialized like a `SendEmailRequest`.
+#[derive(serde::Serialize)]
+pub(crate) struct ExpandedRequest<'a> {
+    pub(crate) emails: Expanded<'a>,
+}
+
+impl Expanded<'_> {
+    pub(crate) fn validate(&self) -> Result<()> {
+        validate_content(self.base)?;
+        let shared =
+            self.base.cc.as_ref().map_or(0, Vec::len) + self.base.bcc.as_ref().map_or(0, Vec::len);
+        for recipient in self.recipients {
+            validate_recipient_count(recipient.to.len() + shared)?;
+            if let Some(webhook_data) = recipient.merged_webhook_data(self.base) {
+                validate_webhook_data(&webhook_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn expand(&self) -> Vec<Email> {
+        self.recipients
+            .iter()
+            .map(|recipient| Email {
+                to: recipient.to.clone(),
+                template_data: recipient
+                    .merged_template_data(self.base)
+                    .map(Cow::into_owned),
+                webhook_data: recipient
+                    .merged_webhook_data(self.base)
+                    .map(Cow::into_owned),
+                ..self.base.clone()
+            })
+            .collect()
+    }
+
+    /// Number of recipients across `to`, `cc` and `bcc` of all emails.
+    pub(crate) fn recipient_count(&self) -> usize {
+        let shared =
+            self.base.cc.as_ref().map_or(0, Vec::len) + self.base.bcc.as_ref().map_or(0, Vec::len);
+        self.recipients
+            .iter()
+            .map(|recipient| recipient.to.len() + shared)
+            .sum()
+    }
+}
+
+impl Serialize for Expanded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.recipients.len()))?;
+        for recipient in self.recipients {
+            seq.serialize_element(&EmailRef::new(self.base, recipient))?;
+        }
+        seq.end()
+    }
+}
+
+/// An expanded email borrowing from the base email; serializes exactly like [`Email`].
+#[derive(serde::Serialize)]
+struct EmailRef<'a> {
+    from: &'a EmailAddress,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from_header: Option<&'a EmailAddress>,
+    to: &'a [EmailAddress],
+    subject: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a EmailAddress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cc: Option<&'a [EmailAddress]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bcc: Option<&'a [EmailAddress]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachments: Option<&'a [Attachment]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<&'a HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_data: Option<Cow<'a, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    send_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    webhook_data: Option<Cow<'a, HashMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracking: Option<&'a Tracking>,
+}
+
+impl<'a> EmailRef<'a> {
+    fn new(base: &'a Email, recipient: &'a Personalization) -> Self {
+        Self {
+            from: &base.from,
+            from_header: base.from_header.as_ref(),
+            to: &recipient.to,
+            subject: &base.subject,
+            text_content: base.text_content.as_deref(),
+            html_content: base.html_content.as_deref(),
+            reply_to: base.reply_to.as_ref(),
+            cc: base.cc.as_deref(),
+            bcc: base.bcc.as_deref(),
+            attachments: base.attachments.as_deref(),
+            headers: base.headers.as_ref(),
+            template_id: base.template_id.as_deref(),
+            template_data: recipient.merged_template_data(base),
+            send_time: base.send_time,
+            webhook_data: recipient.merged_webhook_data(base),
+            tag: base.tag.as_deref(),
+            tracking: base.tracking.as_ref(),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Background queue coalescing individually submitted emails into batched sends.
+
+use crate::client::{LanefulClient, Resend, SendOptions};
+use crate::error::{LanefulError, Result};
+use crate::models::{Email, SendEmailResponse};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+const DEFAULT_MAX_WAIT: Duration = Duration::from_millis(100);
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_CAPACITY: usize = 1000;
+/// Largest concurrency: the task waits for the batches in flight by acquiring every
+/// permit at once, with a `u32` count.
+const MAX_CONCURRENCY: usize = if Semaphore::MAX_PERMITS < u32::MAX as usize {
+    Semaphore::MAX_PERMITS
+} else {
+    u32::MAX as usize
+};
+
+/// Outcome of an email sent through an [`EmailQueue`].
+///
+/// The response to its batch is narrowed to this email: its
+/// [`results`](SendEmailResponse::results) hold only this email's result, if the API
+/// reported one, at index 0. Every email of a failed request shares its error.
+pub type QueueResult = std::result::Result<SendEmailResponse, Arc<LanefulError>>;
+
+struct Job {
+    email: Email,
+    reply: oneshot::Sender<QueueResult>,
+}
+
+/// Builder for [`EmailQueue`].
+#[derive(Debug, Clone)]
+pub struct EmailQueueBuilder {
+    client: LanefulClient,
+    options: SendOptions,
+    max_batch_size: usize,
+    max_wait: Duration,
+    concurrency: usize,
+    capacity: usize,
+}
+
+impl EmailQueueBuilder {
+    /// Set the maximum number of emails per request (default: 100).
+    pub fn max_batch_size(mut self, size: usize) -> Self {
+        self.max_batch_size = size.max(1);
+        self
+    }
+
+    /// Set how long the first email of a batch waits for others to join it
+    /// (default: 100ms). A wait too long to represent waits for a full batch.
+    pub fn max_wait(mut self, wait: Duration) -> Self {
+        self.max_wait = wait;
+        self
+    }
+
+    /// Set the number of requests in flight at once (default: 4).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+        self
+    }
+
+    /// Set the number of emails that can wait in the queue before
+    /// [`enqueue`](EmailQueue::enqueue) waits for room (default: 1000).
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.clamp(1, Semaphore::MAX_PERMITS);
+        self
+    }
+
+    /// Set the options used for every batch.
+    ///
+    /// Their [idempotency key](SendOptions::idempotency_key), if any, is not used:
+    /// batches hold whichever emails happen to be queued together, so no key could tell
+    /// a repeat from another batch. Enable
+    /// [`auto_idempotency_keys`](LanefulClient::auto_idempotency_keys) on the client
+    /// instead.
+    pub fn send_options(mut self, options: SendOptions) -> Self {
+        self.options = options.without_idempotency_key();
+        self
+    }
+
+    /// Start the background task and return a handle to the queue.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn build(self) -> EmailQueue {
+        let (jobs, receiver) = mpsc::channel(self.capacity);
+        let (close, closing) = watch::channel(false);
+        let (done, finished) = watch::channel(false);
+        tokio::spawn(run(self, receiver, closing, done));
+        EmailQueue {
+            jobs,
+            close: Arc::new(close),
+            finished,
+        }
+    }
+}
+
+/// A cloneable handle to a background task sending emails in batches.
+///
+/// Emails submitted from anywhere in an application, e.g. from many web request
+/// handlers, are coalesced into batched requests: a batch is sent once it holds
+/// [`max_batch_size`](EmailQueueBuilder::max_batch_size) emails or its first email has
+/// waited [`max_wait`](EmailQueueBuilder::max_wait). Each email gets its own outcome. If
+/// the API rejects a whole batch because of its content, its emails are sent again one by
+/// one so that a bad email only fails its own submission.
+///
+/// When every handle is dropped, or after [`close`](Self::close), the emails already
+/// queued are still sent.
+///
+/// Requires the `queue` feature and a Tokio runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use laneful_rs::{Email, EmailQueue, LanefulClient};
+/// use std::time::Duration;
+///
+/// # async fn example() {
+/// let client = LanefulClient::new("https://custom-endpoint.api.laneful.com", "my-api-key").unwrap();
+/// let queue = EmailQueue::builder(client)
+///     .max_batch_size(50)
+///     .max_wait(Duration::from_millis(200))
+///     .build();
+///
+/// let email = Email::builder()
+///     .from("sender@example.com", None)
+///     .to("recipient@example.com", None)
+///     .subject("Welcome")
+///     .text_content("Thanks for signing up!")
+///     .build()
+///     .unwrap();
+///
+/// // Fire and forget: the email is sent even if the returned future is dropped.
+/// let queued = queue.enqueue(email).await.unwrap();
+/// // Or wait for its outcome.
+/// let response = queued.await;
+///
+/// queue.close();
+/// queue.closed().await;
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EmailQueue {
+    jobs: mpsc::Sender<Job>,
+    close: Arc<watch::Sender<bool>>,
+    finished: watch::Receiver<bool>,
+}
+
+impl EmailQueue {
+    /// Create a builder for a queue sending through `client`.
+    pub fn builder(client: LanefulClient) -> EmailQueueBuilder {
+        EmailQueueBuilder {
+            client,
+            options: SendOptions::default(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_wait: DEFAULT_MAX_WAIT,
+            concurrency: DEFAULT_CONCURRENCY,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Create a queue with the default settings.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new(client: LanefulClient) -> Self {
+        Self::builder(client).build()
+    }
+
+    /// Add an email to the queue, waiting while it is full.
+    ///
+    /// The returned future resolves to the email's outcome; the email is sent whether or
+    /// not it is awaited. Fails with [`LanefulError::Cancelled`] if the queue is closed.
+    pub async fn enqueue(&self, email: Email) -> Result<QueuedEmail> {
+        let (reply, receiver) = oneshot::channel();
+        self.jobs
+            .send(Job { email, reply })
+            .await
+            .map_err(|_| LanefulError::Cancelled)?;
+        Ok(QueuedEmail { receiver })
+    }
+
+    /// Add an email to the queue and wait for its outcome.
+    pub async fn send(&self, email: Email) -> QueueResult {
+        match self.enqueue(email).await {
+            Ok(queued) => queued.await,
+            Err(err) => Err(Arc::new(err)),
+        }
+    }
+
+    /// Stop accepting emails. Emails already queued are still sent; wait for them with
+    /// [`closed`](Self::closed).
+    pub fn close(&self) {
+        self.close.send_replace(true);
+    }
+
+    /// Wait until the queue is closed and every queued email has been sent.
+    pub async fn closed(&self) {
+        let mut finished = self.finished.clone();
+        // An error means the task is gone, which is just as final.
+        let _ = finished.wait_for(|finished| *finished).await;
+    }
+}
+
+/// The outcome of an email added to an [`EmailQueue`].
+///
+/// Resolves to [`LanefulError::Cancelled`] if the queue's task stops before sending it.
+#[derive(Debug)]
+pub struct QueuedEmail {
+    receiver: oneshot::Receiver<QueueResult>,
+}
+
+impl Future for QueuedEmail {
+    type Output = QueueResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<QueueResult> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|result| result.unwrap_or_else(|_| Err(Arc::new(LanefulError::Cancelled))))
+    }
+}
+
+async fn run(
+    settings: EmailQueueBuilder,
+    mut receiver: mpsc::Receiver<Job>,
+    mut closing: watch::Receiver<bool>,
+    done: watch::Sender<bool>,
+) {
+    let client = Arc::new(settings.client);
+    let options = Arc::new(settings.options);
+    let permits = Arc::new(Semaphore::new(settings.concurrency));
+    let mut closed = false;
+
+    loop {
+        let first = if closed {
+            receiver.recv().await
+        } else {
+            tokio::select! {
+                job = receiver.recv() => job,
+                _ = wait_closing(&mut closing) => {
+                    // Refuse new emails but keep draining those already queued.
+                    receiver.close();
+                    closed = true;
+                    continue;
+                }
+            }
+        };
+        let Some(first) = first else {
+            break;
+        };
+
+        let mut batch = vec![first];
+        let deadline = Instant::now().checked_add(settings.max_wait);
+        while batch.len() < settings.max_batch_size {
+            let job = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, receiver.recv())
+                    .await
+                    .unwrap_or(None),
+                None => receiver.recv().await,
+            };
+            match job {
+                Some(job) => batch.push(job),
+                None => break,
+            }
+        }
+
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let client = client.clone();
+        let options = options.clone();
+        tokio::spawn(async move {
+            send_batch(&client, &options, batch).await;
+            drop(permit);
+        });
+    }
+
+    // Wait for the batches in flight.
+    let _ = permits.acquire_many(settings.concurrency as u32).await;
+    done.send_replace(true);
+}
+
+/// Resolve once the queue is closed.
+async fn wait_closing(closing: &mut watch::Receiver<bool>) {
+    // Without any handle left the channel closes instead, which `recv` observes.
+    if closing.wait_for(|closing| *closing).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+async fn send_batch(client: &LanefulClient, options: &SendOptions, batch: Vec<Job>) {
+    // Invalid emails fail on their own rather than with the whole batch.
+    let mut emails = Vec::with_capacity(batch.len());
+    let mut replies = Vec::with_capacity(batch.len());
+    for job in batch {
+        match job.email.validate() {
+            Ok(()) => {
+                emails.push(job.email);
+                replies.push(job.reply);
+            }
+            Err(err) => {
+                let _ = job.reply.send(Err(Arc::new(err)));
+            }
+        }
+    }
+    if emails.is_empty() {
+        return;
+    }
+
+    match client.send_batch_async(emails, options).await {
+        Ok(response) => {
+            for (index, reply) in replies.into_iter().enumerate() {
+                let _ = reply.send(Ok(response.for_email(index)));
+            }
+        }
+        // One email may have caused the rejection of the whole request: send them one at
+        // a time so only the offending email fails.
+        Err((_, Some(Resend::Prepared(emails)))) if emails.len() > 1 => {
+            for (email, reply) in emails.into_iter().zip(replies) {
+                let result = client.send_prepared_email_async(email, options).await;
+                let _ = reply.send(result.map_err(Arc::new));
+            }
+        }
+        // Middleware or the recipient policy dropped emails: sent one at a time, the
+        // dropped ones fail with their own error.
+        Err((_, Some(Resend::Original(emails)))) => {
+            for (email, reply) in emails.into_iter().zip(replies) {
+                let result = client.send_with_options_async(vec![email], options).await;
+                let _ = reply.send(result.map_err(Arc::new));
+            }
+        }
+        Err((err, _)) => {
+            let err = Arc::new(err);
+            for reply in replies {
+                let _ = reply.send(Err(err.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Client-side rate limiting.
+//!
+//! A [`RateLimiter`] holds token buckets for requests per second and emails per second.
+//! It is cheap to clone and can be shared by several clients (and by sync and async
+//! callers at the same time). The effective rate backs off when the API answers with
+//! `429 Too Many Requests` or reports an exhausted quota, and recovers gradually on
+//! successful sends.
+
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+
+const DEFAULT_PAUSE: Duration = Duration::from_secs(1);
+/// Longest wait or pause, for rates and server-sent delays too large to represent.
+const MAX_PAUSE: Duration = Duration::from_secs(24 * 3600);
+const DEFAULT_TRANSACTIONAL_RESERVE: f64 = 0.2;
+const MIN_FACTOR: f64 = 0.05;
+const BACKOFF_FACTOR: f64 = 0.5;
+const RECOVERY_STEP: f64 = 0.05;
+const YIELD_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Priority lane of a send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// Latency-sensitive mail (password resets, receipts). Always served first.
+    #[default]
+    Transactional,
+    /// Campaigns and batch jobs. Yields to waiting transactional sends and never uses
+    /// the capacity reserved for them.
+    Bulk,
+}
+
+/// Builder for [`RateLimiter`].
+#[derive(Debug, Clone)]
+pub struct RateLimiterBuilder {
+    requests_per_second: Option<f64>,
+    emails_per_second: Option<f64>,
+    burst: Option<f64>,
+    transactional_reserve: f64,
+}
+
+impl Default for RateLimiterBuilder {
+    fn default() -> Self {
+        Self {
+            requests_per_second: None,
+            emails_per_second: None,
+            burst: None,
+            transactional_reserve: DEFAULT_TRANSACTIONAL_RESERVE,
+        }
+    }
+}
+
+impl RateLimiterBuilder {
+    /// Limit the number of API requests per second.
+    pub fn requests_per_second(mut self, rate: f64) -> Self {
+        self.requests_per_second = Some(rate);
+        self
+    }
+
+    /// Limit the number of emails per second, counted across batches.
+    pub fn emails_per_second(mut self, rate: f64) -> Self {
+        self.emails_per_second = Some(rate);
+        self
+    }
+
+    /// Set the bucket size as a number of seconds worth of tokens (default: 1.0).
+    pub fn burst(mut self, seconds: f64) -> Self {
+        self.burst = Some(seconds);
+        self
+    }
+
+    /// Share of each bucket that bulk sends may not use (default: 0.2).
+    pub fn transactional_reserve(mut self, share: f64) -> Self {
+        self.transactional_reserve = share.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Build the rate limiter.
+    pub fn build(self) -> RateLimiter {
+        let burst = self.burst.unwrap_or(1.0).max(f64::EPSILON);
+        let bucket = |rate: Option<f64>| {
+            rate.filter(|rate| *rate > 0.0).map(|rate| Bucket {
+                rate,
+                capacity: (rate * burst).max(1.0),
+                tokens: (rate * burst).max(1.0),
+            })
+        };
+
+        RateLimiter {
+            inner: Arc::new(Mutex::new(State {
+                requests: bucket(self.requests_per_second),
+                emails: bucket(self.emails_per_second),
+                transactional_reserve: self.transactional_reserve,
+                last_refill: Instant::now(),
+                factor: 1.0,
+                paused_until: None,
+                waiting_transactional: 0,
+            })),
+        }
+    }
+}
+
+/// Shared token-bucket rate limiter with priority lanes.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::{Priority, RateLimiter};
+///
+/// let limiter = RateLimiter::builder()
+///     .requests_per_second(10.0)
+///     .emails_per_second(500.0)
+///     .build();
+///
+/// limiter.acquire_blocking(1, Priority::Transactional);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+}
+
+#[derive(Debug)]
+struct State {
+    requests: Option<Bucket>,
+    emails: Option<Bucket>,
+    transactional_reserve: f64,
+    last_refill: Instant,
+    /// Multiplier applied to the configured rates after 429 responses.
+    factor: f64,
+    paused_until: Option<Instant>,
+    waiting_transactional: usize,
+}
+
+impl State {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        for bucket in [&mut self.requests, &mut self.emails].into_iter().flatten() {
+            bucket.tokens =
+                (bucket.tokens + bucket.rate * self.factor * elapsed).min(bucket.capacity);
+        }
+    }
+
+    /// Take tokens for one request of `emails` emails, or return how long to wait.
+    fn try_acquire(&mut self, emails: usize, priority: Priority) -> Option<Duration> {
+        let now = Instant::now();
+        self.refill(now);
+
+        if let Some(paused_until) = self.paused_until {
+            if paused_until > now {
+                return Some(paused_until - now);
+            }
+            self.paused_until = None;
+        }
+
+        if priority == Priority::Bulk && self.waiting_transactional > 0 {
+            return Some(YIELD_INTERVAL);
+        }
+
+        let reserve = match priority {
+            Priority::Transactional => 0.0,
+            Priority::Bulk => self.transactional_reserve,
+        };
+        let factor = self.factor;
+        let wait = [(&self.requests, 1.0), (&self.emails, emails as f64)]
+            .into_iter()
+            .filter_map(|(bucket, needed)| {
+                let bucket = bucket.as_ref()?;
+                // Requests larger than the bucket only need a full bucket and go into debt.
+                let required =
+                    (needed.min(bucket.capacity) + bucket.capacity * reserve).min(bucket.capacity);
+                let deficit = (required - bucket.tokens).max(0.0);
+                let wait = Duration::try_from_secs_f64(deficit / (bucket.rate * factor));
+                Some(wait.map_or(MAX_PAUSE, |wait| wait.min(MAX_PAUSE)))
+            })
+            .max()
+            .unwrap_or_default();
+
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+
+        if let Some(bucket) = &mut self.requests {
+            bucket.tokens -= 1.0;
+        }
+        if let Some(bucket) = &mut self.emails {
+            bucket.tokens -= emails as f64;
+        }
+        None
+    }
+
+    fn pause_for(&mut self, duration: Duration) {
+        let now = Instant::now();
+        let until = now.checked_add(duration).unwrap_or_else(|| now + MAX_PAUSE);
+        if self.paused_until.is_none_or(|current| current < until) {
+            self.paused_until = Some(until);
+        }
+    }
+}
+
+/// Keeps the transactional waiter count accurate even if an async wait is cancelled.
+struct WaitingGuard<'a> {
+    limiter: &'a RateLimiter,
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.limiter.state().waiting_transactional -= 1;
+    }
+}
+
+impl RateLimiter {
+    /// Create a rate limiter builder.
+    pub fn builder() -> RateLimiterBuilder {
+        RateLimiterBuilder::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn register_waiter(&self, priority: Priority) -> Option<WaitingGuard<'_>> {
+        if priority != Priority::Transactional {
+            return None;
+        }
+        self.state().waiting_transactional += 1;
+        Some(WaitingGuard { limiter: self })
+    }
+
+    /// Try to take capacity for one request carrying `emails` emails without waiting.
+    pub fn try_acquire(&self, emails: usize, priority: Priority) -> bool {
+        self.state().try_acquire(emails, priority).is_none()
+    }
+
+    /// Block the current thread until one request carrying `emails` emails may be sent.
+    pub fn acquire_blocking(&self, emails: usize, priority: Priority) {
+        let Some(mut wait) = self.state().try_acquire(emails, priority) else {
+            return;
+        };
+        let _guard = self.register_waiter(priority);
+        loop {
+            std::thread::sleep(wait);
+            match self.state().try_acquire(emails, priority) {
+                Some(next) => wait = next,
+                None => return,
+            }
+        }
+    }
+
+    /// Wait until one request carrying `emails` emails may be sent.
+    #[cfg(feature = "async")]
+    pub async fn acquire(&self, emails: usize, priority: Priority) {
+        let Some(mut wait) = self.state().try_acquire(emails, priority) else {
+            return;
+        };
+        let _guard = self.register_waiter(priority);
+        loop {
+            tokio::time::sleep(wait).await;
+            match self.state().try_acquire(emails, priority) {
+                Some(next) => wait = next,
+                None => return,
+            }
+        }
+    }
+
+    /// Pause all sends and halve the effective rate after the API rejected a request.
+    pub fn on_rate_limited(&self, retry_after: Option<Duration>) {
+        let mut state = self.state();
+        state.factor = (state.factor * BACKOFF_FACTOR).max(MIN_FACTOR);
+        state.pause_for(retry_after.unwrap_or(DEFAULT_PAUSE));
+    }
+
+    /// Gradually restore the effective rate after a successful send.
+    pub fn on_success(&self) {
+        let mut state = self.state();
+        state.factor = (state.factor + RECOVERY_STEP).min(1.0);
+    }
+
+    /// Current multiplier applied to the configured rates (1.0 when not throttled).
+    pub fn rate_factor(&self) -> f64 {
+        self.state().factor
+    }
+
+    /// Adapt to an API response: 429s, `Retry-After` and `X-RateLimit-*` headers.
+    pub(crate) fn observe(&self, status: StatusCode, headers: &HeaderMap) {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            self.on_rate_limited(retry_after(headers));
+            return;
+        }
+
+        let exhausted = header_u64(headers, RATE_LIMIT_REMAINING_HEADER) == Some(0);
+        if exhausted && let Some(reset) = rate_limit_reset(headers) {
+            self.state().pause_for(reset);
+        }
+
+        if status.is_success() {
+            self.on_success();
+        }
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Parse `Retry-After` given in seconds.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    header_u64(headers, RETRY_AFTER.as_str()).map(Duration::from_secs)
+}
+
+/// Parse `X-RateLimit-Reset` as either seconds from now or a Unix timestamp.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<Duration> {
+    let reset = header_u64(headers, RATE_LIMIT_RESET_HEADER)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    if reset > now / 2 {
+        Some(Duration::from_secs(reset.saturating_sub(now)))
+    } else {
+        Some(Duration::from_secs(reset))
+    }
+}
@@ -0,0 +1,240 @@
+//! Recipient redirect and allowlist modes that keep staging environments from emailing
+//! real customers.
+
+use crate::models::{Email, EmailAddress};
+use std::fmt;
+use std::sync::Arc;
+
+/// Header listing the recipients an email was redirected away from.
+pub const ORIGINAL_RECIPIENTS_HEADER: &str = "X-Original-Recipients";
+
+type ReportFn = dyn Fn(&RecipientReport) + Send + Sync;
+
+#[derive(Debug, Clone)]
+enum Mode {
+    Redirect(EmailAddress),
+    Allowlist,
+}
+
+/// Rewrites or drops recipients before emails are sent.
+///
+/// Installed with [`LanefulClient::recipient_policy`](crate::LanefulClient::recipient_policy),
+/// the policy applies to every send, after [middleware](crate::Middleware). Recipients
+/// matching an allowed domain or address are always kept; the others are handled by the
+/// mode:
+///
+/// - [`redirect_to`](Self::redirect_to) replaces them in `to`, `cc` and `bcc` with one
+///   catch-all address in `to`. The original recipients are kept in the
+///   [`X-Original-Recipients`](ORIGINAL_RECIPIENTS_HEADER) header and as a subject
+///   prefix, e.g. `[to: user@example.com] Welcome`.
+/// - [`allowlist`](Self::allowlist) drops them. Emails left without any recipient are
+///   not sent; if no email is left, the send fails with
+///   [`LanefulError::ValidationError`](crate::LanefulError::ValidationError).
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::{Email, RecipientPolicy};
+///
+/// let policy = RecipientPolicy::redirect_to("qa@example.com")
+///     .allow_domain("example.com")
+///     .on_report(|report| eprintln!("redirected {} recipients", report.redirected.len()));
+///
+/// let mut emails = vec![
+///     Email::builder()
+///         .from("noreply@example.com", None)
+///         .to("customer@gmail.com", None)
+///         .subject("Welcome")
+///         .text_content("Hello!")
+///         .build()
+///         .unwrap(),
+/// ];
+/// let report = policy.apply(&mut emails);
+///
+/// assert_eq!(report.redirected, vec!["customer@gmail.com".to_string()]);
+/// assert_eq!(emails[0].to[0].email, "qa@example.com");
+/// assert_eq!(emails[0].subject, "[to: customer@gmail.com] Welcome");
+/// ```
+#[derive(Clone)]
+pub struct RecipientPolicy {
+    mode: Mode,
+    allowed_domains: Vec<String>,
+    allowed_addresses: Vec<String>,
+    on_report: Option<Arc<ReportFn>>,
+}
+
+/// What a [`RecipientPolicy`] changed in a send.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecipientReport {
+    /// Recipients replaced by the catch-all address.
+    pub redirected: Vec<String>,
+    /// Recipients dropped by the allowlist.
+    pub dropped: Vec<String>,
+    /// Emails removed because no recipient was left.
+    pub removed_emails: usize,
+}
+
+impl RecipientReport {
+    /// Whether the policy left the emails unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.redirected.is_empty() && self.dropped.is_empty() && self.removed_emails == 0
+    }
+}
+
+impl RecipientPolicy {
+    /// Redirect every recipient that is not allowed to `catch_all`.
+    pub fn redirect_to(catch_all: impl Into<String>) -> Self {
+        Self::with_mode(Mode::Redirect(EmailAddress::new(catch_all)))
+    }
+
+    /// Drop every recipient that is not allowed.
+    pub fn allowlist() -> Self {
+        Self::with_mode(Mode::Allowlist)
+    }
+
+    fn with_mode(mode: Mode) -> Self {
+        Self {
+            mode,
+            allowed_domains: Vec::new(),
+            allowed_addresses: Vec::new(),
+            on_report: None,
+        }
+    }
+
+    /// Allow recipients at `domain` (case-insensitive, exact domain match).
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_domains
+            .push(domain.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Allow a single recipient address (case-insensitive).
+    pub fn allow_address(mut self, address: impl Into<String>) -> Self {
+        self.allowed_addresses
+            .push(address.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Call `callback` with the report of every send the policy changed.
+    pub fn on_report(
+        mut self,
+        callback: impl Fn(&RecipientReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_report = Some(Arc::new(callback));
+        self
+    }
+
+    /// Whether `address` is allowed through unchanged.
+    pub fn is_allowed(&self, address: &str) -> bool {
+        let address = address.to_ascii_lowercase();
+        let domain = address.rsplit_once('@').map_or("", |(_, domain)| domain);
+        self.allowed_addresses.contains(&address)
+            || self.allowed_domains.iter().any(|allowed| allowed == domain)
+    }
+
+    /// Apply the policy to `emails`, removing emails left without any recipient.
+    ///
+    /// The [report callback](Self::on_report) is called if anything changed.
+    pub fn apply(&self, emails: &mut Vec<Email>) -> RecipientReport {
+        let mut report = RecipientReport::default();
+
+        emails.retain_mut(|email| {
+            match &self.mode {
+                Mode::Redirect(catch_all) => self.redirect(email, catch_all, &mut report),
+                Mode::Allowlist => self.filter(email, &mut report),
+            }
+            if email.to.is_empty() && email.cc.is_none() && email.bcc.is_none() {
+                report.removed_emails += 1;
+                return false;
+            }
+            true
+        });
+
+        if !report.is_empty()
+            && let Some(callback) = &self.on_report
+        {
+            callback(&report);
+        }
+        report
+    }
+
+    fn redirect(&self, email: &mut Email, catch_all: &EmailAddress, report: &mut RecipientReport) {
+        let mut originals = Vec::new();
+        for (field, recipients) in [
+            ("to", Some(&mut email.to)),
+            ("cc", email.cc.as_mut()),
+            ("bcc", email.bcc.as_mut()),
+        ] {
+            let Some(recipients) = recipients else {
+                continue;
+            };
+            let redirected = self.take_disallowed(recipients);
+            if !redirected.is_empty() {
+                originals.push(format!("{field}: {}", redirected.join(", ")));
+                report.redirected.extend(redirected);
+            }
+        }
+        normalize(&mut email.cc);
+        normalize(&mut email.bcc);
+
+        if originals.is_empty() {
+            return;
+        }
+        let originals = originals.join("; ");
+
+        if !email
+            .to
+            .iter()
+            .any(|to| to.email.eq_ignore_ascii_case(&catch_all.email))
+        {
+            email.to.push(catch_all.clone());
+        }
+        email.subject = format!("[{originals}] {}", email.subject);
+        email
+            .headers
+            .get_or_insert_default()
+            .insert(ORIGINAL_RECIPIENTS_HEADER.into(), originals);
+    }
+
+    fn filter(&self, email: &mut Email, report: &mut RecipientReport) {
+        report.dropped.extend(self.take_disallowed(&mut email.to));
+        for recipients in [email.cc.as_mut(), email.bcc.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            report.dropped.extend(self.take_disallowed(recipients));
+        }
+        normalize(&mut email.cc);
+        normalize(&mut email.bcc);
+    }
+
+    /// Remove the recipients that are not allowed, returning their addresses.
+    fn take_disallowed(&self, recipients: &mut Vec<EmailAddress>) -> Vec<String> {
+        let mut taken = Vec::new();
+        recipients.retain(|recipient| {
+            let allowed = self.is_allowed(&recipient.email);
+            if !allowed {
+                taken.push(recipient.email.clone());
+            }
+            allowed
+        });
+        taken
+    }
+}
+
+/// Omit empty `cc`/`bcc` lists from the request.
+fn normalize(recipients: &mut Option<Vec<EmailAddress>>) {
+    if recipients.as_ref().is_some_and(Vec::is_empty) {
+        *recipients = None;
+    }
+}
+
+impl fmt::Debug for RecipientPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecipientPolicy")
+            .field("mode", &self.mode)
+            .field("allowed_domains", &self.allowed_domains)
+            .field("allowed_addresses", &self.allowed_addresses)
+            .finish_non_exhaustive()
+    }
+}
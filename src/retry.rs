@@ -0,0 +1,79 @@
+//! Retry policy for transient send failures.
+
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How often and how long to wait before retrying a failed send.
+///
+/// Only [retryable](crate::LanefulError::is_retryable) errors are retried. A request
+/// that timed out may already have been accepted, so combine retries with
+/// [idempotency keys](crate::SendOptions::idempotency_key) to avoid duplicates.
+///
+/// In configuration files durations are given in milliseconds:
+///
+/// ```json
+/// { "max_retries": 3, "initial_backoff_ms": 500, "max_backoff_ms": 30000 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (default: 0, i.e. no retries).
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled for every further retry (default: 500ms).
+    #[serde(rename = "initial_backoff_ms", deserialize_with = "duration_ms")]
+    pub initial_backoff: Duration,
+    /// Upper bound for the wait between retries (default: 30s).
+    #[serde(rename = "max_backoff_ms", deserialize_with = "duration_ms")]
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry up to `max_retries` times with the default backoff.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Set the exponential backoff bounds.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Wait before retry number `retry` (starting at 0).
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// Deserialize a duration given in milliseconds.
+pub(crate) fn duration_ms<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+/// Deserialize an optional duration given in milliseconds.
+pub(crate) fn option_duration_ms<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+}
@@ -0,0 +1,66 @@
+//! Secret values (API keys, webhook secrets) that never show up in logs.
+
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A string holding a secret such as an API key or webhook secret.
+///
+/// The value is wiped from memory when dropped, and `Debug`/`Display` print
+/// `[REDACTED]` instead of the secret. Use [`expose_secret`](Self::expose_secret) to
+/// access the value explicitly.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::SecretString;
+///
+/// let key = SecretString::new("my-api-key");
+/// assert_eq!(format!("{key:?}"), "[REDACTED]");
+/// assert_eq!(key.expose_secret(), "my-api-key");
+/// ```
+#[derive(Clone, Default)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    /// Wrap a secret value.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(Zeroizing::new(secret.into()))
+    }
+
+    /// Access the secret value.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<S: Into<String>> From<S> for SecretString {
+    fn from(secret: S) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
@@ -0,0 +1,98 @@
+//! Optional metrics (`metrics` feature) and trace context propagation (`otel` feature).
+//!
+//! Like the `trace` module, everything here compiles to no-ops when the features
+//! are disabled.
+
+use crate::error::LanefulError;
+use reqwest::header::HeaderMap;
+use std::time::Duration;
+
+/// Emails accepted by the API.
+#[cfg(feature = "metrics")]
+const EMAILS_SENT: &str = "laneful_emails_sent_total";
+/// Emails per request.
+#[cfg(feature = "metrics")]
+const BATCH_SIZE: &str = "laneful_batch_size";
+/// Requests that failed after all retries, labelled by error kind.
+#[cfg(feature = "metrics")]
+const SEND_FAILURES: &str = "laneful_send_failures_total";
+/// Duration of HTTP attempts, labelled by endpoint and status.
+#[cfg(feature = "metrics")]
+const REQUEST_DURATION: &str = "laneful_request_duration_seconds";
+/// Retries of failed requests.
+#[cfg(feature = "metrics")]
+const RETRIES: &str = "laneful_retries_total";
+/// Requests answered from the idempotency store without calling the API.
+#[cfg(feature = "metrics")]
+const IDEMPOTENCY_HITS: &str = "laneful_idempotency_hits_total";
+
+/// A request completed successfully.
+#[allow(unused_variables)]
+pub(crate) fn sent(email_count: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(EMAILS_SENT).increment(email_count as u64);
+        metrics::histogram!(BATCH_SIZE).record(email_count as f64);
+    }
+}
+
+/// A request was answered from the idempotency store; not counted as sent.
+pub(crate) fn cached() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(IDEMPOTENCY_HITS).increment(1);
+}
+
+/// A request failed after all retries.
+#[allow(unused_variables)]
+pub(crate) fn failed(err: &LanefulError) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(SEND_FAILURES, "kind" => err.kind()).increment(1);
+}
+
+/// A failed request is retried.
+pub(crate) fn retried() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(RETRIES).increment(1);
+}
+
+/// An HTTP attempt finished with `status`, or without a response.
+#[allow(unused_variables)]
+pub(crate) fn attempt(endpoint: &str, status: Option<u16>, latency: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let status = status.map_or_else(|| "error".to_string(), |status| status.to_string());
+        metrics::histogram!(
+            REQUEST_DURATION,
+            "endpoint" => endpoint.to_string(),
+            "status" => status,
+        )
+        .record(latency.as_secs_f64());
+    }
+}
+
+/// Headers carrying the current OpenTelemetry context, injected with the global
+/// text map propagator (e.g. W3C `traceparent`).
+pub(crate) fn propagation_headers() -> HeaderMap {
+    #[allow(unused_mut)]
+    let mut headers = HeaderMap::new();
+    #[cfg(feature = "otel")]
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject(&mut HeaderInjector(&mut headers));
+    });
+    headers
+}
+
+#[cfg(feature = "otel")]
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+#[cfg(feature = "otel")]
+impl opentelemetry::propagation::Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
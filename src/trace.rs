@@ -0,0 +1,245 @@
+//! Optional `tracing` instrumentation of sends.
+//!
+//! Everything here compiles to no-ops unless the `tracing` feature is enabled, so the
+//! client can instrument its send pipeline without `cfg` attributes at every call site.
+
+use crate::endpoint::EndpointEntry;
+use crate::error::LanefulError;
+use crate::models::Email;
+#[cfg(feature = "tracing")]
+use crate::models::EmailAddress;
+use crate::personalized::Expanded;
+use crate::recipient_policy::RecipientReport;
+use std::time::Duration;
+
+/// Response header carrying the API's request id.
+#[cfg(feature = "tracing")]
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Recipients and subjects of a request, hashed unless PII logging is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct Summary {
+    #[cfg(feature = "tracing")]
+    recipients: String,
+    #[cfg(feature = "tracing")]
+    subjects: String,
+}
+
+impl Summary {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(emails: &[Email], include_pii: bool) -> Self {
+        Self::from_parts(
+            emails.iter().flat_map(|email| {
+                email
+                    .to
+                    .iter()
+                    .chain(email.cc.iter().flatten())
+                    .chain(email.bcc.iter().flatten())
+            }),
+            emails.iter().map(|email| email.subject.as_str()),
+            include_pii,
+        )
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn new(_emails: &[Email], _include_pii: bool) -> Self {
+        Self::default()
+    }
+
+    /// Summary of a [`Personalized`](crate::Personalized) send, without expanding it.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn personalized(personalized: Expanded<'_>, include_pii: bool) -> Self {
+        let base = personalized.base;
+        Self::from_parts(
+            personalized.recipients.iter().flat_map(|recipient| {
+                recipient
+                    .to
+                    .iter()
+                    .chain(base.cc.iter().flatten())
+                    .chain(base.bcc.iter().flatten())
+            }),
+            personalized
+                .recipients
+                .iter()
+                .map(|_| base.subject.as_str()),
+            include_pii,
+        )
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn personalized(_personalized: Expanded<'_>, _include_pii: bool) -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "tracing")]
+    fn from_parts<'a>(
+        recipients: impl Iterator<Item = &'a EmailAddress>,
+        subjects: impl Iterator<Item = &'a str>,
+        include_pii: bool,
+    ) -> Self {
+        let redact = |value: &str| {
+            if include_pii {
+                value.to_string()
+            } else {
+                hash(value)
+            }
+        };
+        let recipients = recipients
+            .map(|address| redact(&address.email.to_lowercase()))
+            .collect::<Vec<_>>()
+            .join(",");
+        let subjects = subjects.map(redact).collect::<Vec<_>>().join(",");
+        Self {
+            recipients,
+            subjects,
+        }
+    }
+}
+
+/// Short, stable digest identifying a value without revealing it.
+#[cfg(feature = "tracing")]
+fn hash(value: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = hex::encode(Sha256::digest(value.as_bytes()));
+    format!("sha256:{}", &digest[..16])
+}
+
+/// A span, or nothing when the `tracing` feature is disabled.
+#[derive(Debug, Clone)]
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+impl Span {
+    /// Span covering one request to the API, including retries.
+    #[allow(unused_variables)]
+    pub(crate) fn send(
+        summary: &Summary,
+        email_count: usize,
+        region: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            inner: tracing::info_span!(
+                "laneful.send",
+                batch_size = email_count,
+                region,
+                idempotency_key,
+                recipients = %summary.recipients,
+                subjects = %summary.subjects,
+            ),
+        }
+    }
+
+    /// Span covering one HTTP attempt against one endpoint.
+    #[allow(unused_variables)]
+    pub(crate) fn attempt(entry: &EndpointEntry, attempt: u32) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            inner: tracing::info_span!(
+                "laneful.request",
+                endpoint = entry.endpoint.base_url(),
+                attempt,
+                status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                request_id = tracing::field::Empty,
+            ),
+        }
+    }
+
+    /// Record the response status and request id on an attempt span.
+    #[allow(unused_variables)]
+    pub(crate) fn record_response(&self, status: u16, headers: &reqwest::header::HeaderMap) {
+        #[cfg(feature = "tracing")]
+        {
+            self.inner.record("status", status);
+            if let Some(request_id) = headers
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+            {
+                self.inner.record("request_id", request_id);
+            }
+        }
+    }
+
+    /// Record the latency of an attempt span.
+    #[allow(unused_variables)]
+    pub(crate) fn record_latency(&self, latency: Duration) {
+        #[cfg(feature = "tracing")]
+        self.inner.record("latency_ms", latency.as_millis() as u64);
+    }
+
+    /// Run `future` inside the span.
+    pub(crate) async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.inner.clone()).await;
+        #[cfg(not(feature = "tracing"))]
+        future.await
+    }
+}
+
+/// A send was answered from the idempotency store.
+pub(crate) fn cached() {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("returning stored response for idempotency key");
+}
+
+/// A recipient policy changed the emails of a send.
+#[allow(unused_variables)]
+pub(crate) fn recipients_rewritten(report: &RecipientReport) {
+    #[cfg(feature = "tracing")]
+    if !report.is_empty() {
+        tracing::info!(
+            redirected = report.redirected.len(),
+            dropped = report.dropped.len(),
+            removed_emails = report.removed_emails,
+            "recipient policy applied"
+        );
+    }
+}
+
+/// An endpoint failed and the next one will be tried.
+#[allow(unused_variables)]
+pub(crate) fn endpoint_failed(entry: &EndpointEntry, err: &LanefulError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        endpoint = entry.endpoint.base_url(),
+        error = %err,
+        "endpoint failed, marking it unhealthy"
+    );
+}
+
+/// A failed send will be retried after `delay`.
+#[allow(unused_variables)]
+pub(crate) fn retry(attempt: u32, delay: Duration, err: &LanefulError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        attempt,
+        delay_ms = delay.as_millis() as u64,
+        error = %err,
+        "send failed, retrying"
+    );
+}
+
+/// A send failed for good.
+#[allow(unused_variables)]
+pub(crate) fn failed(attempts: u32, err: &LanefulError) {
+    #[cfg(feature = "tracing")]
+    tracing::error!(attempts, error = %err, "send failed");
+}
+
+/// Polling the outbox failed; the worker retries after `delay`.
+#[cfg(feature = "outbox")]
+#[allow(unused_variables)]
+pub(crate) fn outbox_poll_failed(errors: u32, delay: Duration, err: &LanefulError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        errors,
+        delay_ms = delay.as_millis() as u64,
+        error = %err,
+        "outbox poll failed, backing off"
+    );
+}
@@ -1,5 +1,6 @@
 //! Webhook signature verification utilities.
 
+use crate::secret::SecretString;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
@@ -40,3 +41,39 @@ pub fn verify_webhook_signature(secret: &str, payload: &[u8], signature: &str) -
     // Constant-time comparison to prevent timing attacks
     expected.as_bytes().ct_eq(signature.as_bytes()).into()
 }
+
+/// Verifies webhook signatures with a secret held in a [`SecretString`].
+///
+/// Unlike passing the secret to [`verify_webhook_signature`] on every call, the verifier
+/// keeps it redacted in `Debug` output and wipes it from memory when dropped.
+///
+/// # Example
+///
+/// ```
+/// use laneful_rs::WebhookVerifier;
+///
+/// let verifier = WebhookVerifier::new("my-webhook-secret");
+/// let payload = br#"{"event":"email.sent"}"#;
+///
+/// if verifier.verify(payload, "expected-signature-hex") {
+///     println!("Webhook signature is valid!");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebhookVerifier {
+    secret: SecretString,
+}
+
+impl WebhookVerifier {
+    /// Create a verifier for the given webhook secret.
+    pub fn new(secret: impl Into<SecretString>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// Verify the signature of a raw webhook payload.
+    pub fn verify(&self, payload: &[u8], signature: &str) -> bool {
+        verify_webhook_signature(self.secret.expose_secret(), payload, signature)
+    }
+}
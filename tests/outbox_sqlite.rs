@@ -0,0 +1,167 @@
+//! Outbox tests against an in-memory SQLite database and a local fake of the API.
+#![cfg(feature = "outbox-sqlite")]
+
+use axum::{Json, Router, http::StatusCode, routing::post};
+use laneful_rs::{Email, LanefulClient, Outbox, OutboxStats, OutboxStatus};
+use serde_json::{Value, json};
+use sqlx::sqlite::{Sqlite, SqlitePool, SqlitePoolOptions};
+use std::time::{Duration, SystemTime};
+
+/// Answer like the API: `400` for subjects containing "bad", `503` for "down".
+async fn send(Json(body): Json<Value>) -> (StatusCode, Json<Value>) {
+    let subjects: Vec<&str> = body["emails"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|email| email["subject"].as_str())
+        .collect();
+    if subjects.iter().any(|subject| subject.contains("bad")) {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid email" })),
+        )
+    } else if subjects.iter().any(|subject| subject.contains("down")) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "unavailable" })),
+        )
+    } else {
+        (StatusCode::OK, Json(json!({ "status": "accepted" })))
+    }
+}
+
+async fn api() -> LanefulClient {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/v1/email/send", post(send));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    LanefulClient::new(format!("http://{addr}"), "test-key").unwrap()
+}
+
+async fn outbox() -> Outbox<Sqlite> {
+    // One connection, so every query sees the same in-memory database.
+    let pool: SqlitePool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let outbox = Outbox::new(pool);
+    outbox.migrate().await.unwrap();
+    outbox
+}
+
+fn email(subject: &str) -> Email {
+    Email::builder()
+        .from("sender@example.com", None)
+        .to("recipient@example.com", None)
+        .subject(subject)
+        .text_content("Hello")
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn sends_only_committed_emails() {
+    let outbox = outbox().await;
+    let worker = outbox.worker(api().await);
+
+    let mut tx = outbox.pool().begin().await.unwrap();
+    outbox
+        .enqueue(&mut *tx, &email("rolled back"))
+        .await
+        .unwrap();
+    tx.rollback().await.unwrap();
+
+    let mut tx = outbox.pool().begin().await.unwrap();
+    let id = outbox.enqueue(&mut *tx, &email("committed")).await.unwrap();
+    tx.commit().await.unwrap();
+
+    assert_eq!(worker.run_once().await.unwrap(), 1);
+    let entry = outbox.get(id).await.unwrap().unwrap();
+    assert_eq!(entry.status, OutboxStatus::Sent);
+    assert_eq!(entry.attempts, 1);
+    assert_eq!(
+        outbox.stats().await.unwrap(),
+        OutboxStats {
+            sent: 1,
+            ..OutboxStats::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn retries_transient_failures_with_backoff() {
+    let outbox = outbox().await;
+    let worker = outbox
+        .worker(api().await)
+        .backoff(Duration::from_secs(60), Duration::from_secs(60));
+    let id = outbox.enqueue(outbox.pool(), &email("down")).await.unwrap();
+
+    assert_eq!(worker.run_once().await.unwrap(), 1);
+    let entry = outbox.get(id).await.unwrap().unwrap();
+    assert_eq!(entry.status, OutboxStatus::Pending);
+    assert_eq!(entry.attempts, 1);
+    assert!(entry.last_error.is_some());
+    assert!(entry.available_at > SystemTime::now());
+
+    // Not due yet.
+    assert_eq!(worker.run_once().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn dead_letters_after_max_attempts() {
+    let outbox = outbox().await;
+    let worker = outbox
+        .worker(api().await)
+        .max_attempts(2)
+        .backoff(Duration::ZERO, Duration::ZERO);
+    let id = outbox.enqueue(outbox.pool(), &email("down")).await.unwrap();
+
+    worker.run_once().await.unwrap();
+    worker.run_once().await.unwrap();
+    let entry = outbox.get(id).await.unwrap().unwrap();
+    assert_eq!(entry.status, OutboxStatus::Dead);
+    assert_eq!(entry.attempts, 2);
+    assert_eq!(outbox.dead_letters(10).await.unwrap().len(), 1);
+
+    assert!(outbox.requeue(id).await.unwrap());
+    let entry = outbox.get(id).await.unwrap().unwrap();
+    assert_eq!(entry.status, OutboxStatus::Pending);
+}
+
+#[tokio::test]
+async fn dead_letters_permanent_rejections_at_once() {
+    let outbox = outbox().await;
+    let worker = outbox.worker(api().await);
+    let id = outbox.enqueue(outbox.pool(), &email("bad")).await.unwrap();
+
+    worker.run_once().await.unwrap();
+    let entry = outbox.get(id).await.unwrap().unwrap();
+    assert_eq!(entry.status, OutboxStatus::Dead);
+    assert_eq!(entry.attempts, 1);
+}
+
+#[tokio::test]
+async fn long_leases_do_not_overflow() {
+    let outbox = outbox().await;
+    let worker = outbox.worker(api().await).lease(Duration::MAX);
+    outbox
+        .enqueue(outbox.pool(), &email("hello"))
+        .await
+        .unwrap();
+
+    assert_eq!(worker.run_once().await.unwrap(), 1);
+    assert_eq!(outbox.stats().await.unwrap().sent, 1);
+}
+
+#[tokio::test]
+async fn worker_survives_database_errors() {
+    let outbox = outbox().await;
+    let worker = outbox
+        .worker(api().await)
+        .poll_interval(Duration::from_millis(10));
+    outbox.pool().close().await;
+
+    let shutdown = tokio::time::sleep(Duration::from_millis(200));
+    worker.run(shutdown).await.unwrap();
+}